@@ -0,0 +1,128 @@
+//
+// ned, https://github.com/nevdelap/ned, backup.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::{NedResult, StringError};
+use crate::parameters::Parameters;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Backup {
+    Suffix(String),
+    Numbered,
+}
+
+impl FromStr for Backup {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Ok(Backup::Suffix(".orig".to_string())),
+            "numbered" => Ok(Backup::Numbered),
+            _ if s.contains(['/', '\\']) => Err(StringError {
+                err: format!("invalid backup suffix {}", s),
+            }),
+            _ => Ok(Backup::Suffix(s.to_string())),
+        }
+    }
+}
+
+impl Backup {
+    /// Whether the file name is one that this backup scheme would give a backup.
+    pub fn is_backup_file_name(&self, file_name: &str) -> bool {
+        match self {
+            Backup::Suffix(suffix) => !suffix.is_empty() && file_name.ends_with(suffix.as_str()),
+            Backup::Numbered => backup_number(file_name).is_some(),
+        }
+    }
+}
+
+/// Copy the file at path to its backup, alongside it, or in the same relative place under
+/// --backup-dir, returning the path of the backup.
+pub fn backup_file(parameters: &Parameters, path: &Path) -> NedResult<PathBuf> {
+    let backup = parameters
+        .backup
+        .as_ref()
+        .expect("Bug, only called when backing up.");
+    let dir = match parameters.backup_dir {
+        Some(ref backup_dir) => {
+            let dir = match path.parent() {
+                Some(parent) => backup_dir.join(mirrored_path(parent)),
+                None => backup_dir.to_path_buf(),
+            };
+            fs::create_dir_all(&dir)?;
+            dir
+        }
+        None => path.parent().unwrap_or_else(|| Path::new("")).to_path_buf(),
+    };
+    let file_name = path.file_name().unwrap_or_default();
+    let backup_path = match backup {
+        Backup::Suffix(suffix) => {
+            let mut backup_name = file_name.to_os_string();
+            backup_name.push(suffix);
+            dir.join(backup_name)
+        }
+        Backup::Numbered => dir.join(next_numbered_name(&dir, &file_name.to_os_string())?),
+    };
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// The path with the parts that would take it outside of the backup directory removed, so
+/// that the backups of files under the current directory or elsewhere all go under it.
+fn mirrored_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// The name for the next numbered backup, being file_name.~N~ where N is one more than the
+/// highest existing backup of file_name in dir.
+fn next_numbered_name(dir: &Path, file_name: &OsString) -> NedResult<OsString> {
+    let prefix = format!("{}.~", file_name.to_string_lossy());
+    let mut highest = 0;
+    let read_dir = fs::read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })?;
+    for entry in read_dir.flatten() {
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(number) = entry_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix('~'))
+            .and_then(|number| number.parse::<usize>().ok())
+        {
+            highest = std::cmp::max(highest, number);
+        }
+    }
+    let mut backup_name = file_name.clone();
+    backup_name.push(format!(".~{}~", highest + 1));
+    Ok(backup_name)
+}
+
+/// The N of a file name ending in .~N~.
+fn backup_number(file_name: &str) -> Option<usize> {
+    let rest = file_name.strip_suffix('~')?;
+    let start = rest.rfind(".~")?;
+    rest[start + 2..].parse::<usize>().ok()
+}
@@ -0,0 +1,55 @@
+//
+// ned, https://github.com/nevdelap/ned, binary.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::encoding::has_utf16_bom;
+use crate::ned_error::StringError;
+use std::str::FromStr;
+
+/// How much of a file is looked at to decide whether it is binary.
+pub const DETECTION_LEN: usize = 8192;
+
+/// What is done with binary files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binary {
+    Skip,
+    Report,
+    Text,
+}
+
+impl FromStr for Binary {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Binary::Skip),
+            "report" => Ok(Binary::Report),
+            "text" => Ok(Binary::Text),
+            _ => Err(StringError {
+                err: format!("invalid binary option {}", s),
+            }),
+        }
+    }
+}
+
+/// Whether the content is binary, which it is taken to be if it has a NUL in its first block,
+/// unless it is UTF-16, which has them in its text.
+pub fn is_binary(content: &[u8]) -> bool {
+    !has_utf16_bom(content) && content[..content.len().min(DETECTION_LEN)].contains(&0)
+}
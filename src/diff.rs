@@ -0,0 +1,346 @@
+//
+// ned, https://github.com/nevdelap/ned, diff.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::NedResult;
+use crate::parameters::Parameters;
+use ansi_term::Colour::{Cyan, Green, Red};
+use ansi_term::Style;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+    // The edit, and the index of the line in the old or new lines it refers to.
+    lines: Vec<(Edit, usize)>,
+}
+
+/// Write a unified diff of old and new, with --- and +++ headers naming the file, and @@ hunks
+/// with the number of context lines given by the parameters. Nothing is written if old and new
+/// are the same.
+pub fn write_unified_diff(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &str,
+    old: &str,
+    new: &str,
+) -> NedResult<()> {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<&str>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<&str>>();
+    let edits = edit_script(&old_lines, &new_lines);
+    let hunks = make_hunks(&edits, parameters.change_context);
+    if hunks.is_empty() {
+        return Ok(());
+    }
+
+    let header = format!("--- {}\n+++ {}\n", file_name, file_name);
+    write_colored(output, parameters, Style::new().bold(), &header)?;
+    for hunk in hunks {
+        let range = format!(
+            "@@ -{} +{} @@\n",
+            format_range(hunk.old_start, hunk.old_len),
+            format_range(hunk.new_start, hunk.new_len)
+        );
+        write_colored(output, parameters, Style::from(Cyan), &range)?;
+        for (edit, index) in hunk.lines {
+            let (prefix, line, style) = match edit {
+                Edit::Equal => (" ", old_lines[index], Style::new()),
+                Edit::Delete => ("-", old_lines[index], Style::from(Red)),
+                Edit::Insert => ("+", new_lines[index], Style::from(Green)),
+            };
+            let text = format!("{}{}", prefix, line.strip_suffix('\n').unwrap_or(line));
+            write_colored(output, parameters, style, &text)?;
+            output.write_all(b"\n")?;
+            if !line.ends_with('\n') {
+                output.write_all(b"\\ No newline at end of file\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_colored(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    style: Style,
+    text: &str,
+) -> NedResult<()> {
+    if parameters.colors && style != Style::new() {
+        output.write_all(&style.paint(text).to_string().into_bytes())?;
+    } else {
+        output.write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Format a hunk range the way diff -u does, the line number alone for a single line, and
+/// the line before the range when it is empty.
+fn format_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Group the edits into hunks, each with up to context equal lines around its changes. Hunks
+/// whose context would overlap or touch are merged.
+fn make_hunks(edits: &[(Edit, usize, usize)], context: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::<Hunk>::new();
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, (edit, _, _))| *edit != Edit::Equal)
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+    let mut change = 0;
+    while change < changes.len() {
+        let start = changes[change].saturating_sub(context);
+        let mut end = changes[change];
+        // Take in following changes while the equal lines between them are no more than
+        // twice the context, so that the context of the two doesn't overlap.
+        while change + 1 < changes.len() && changes[change + 1] - end <= 2 * context + 1 {
+            change += 1;
+            end = changes[change];
+        }
+        let end = std::cmp::min(edits.len(), end + context + 1);
+        let (_, old_start, new_start) = edits[start];
+        let mut hunk = Hunk {
+            old_start,
+            old_len: 0,
+            new_start,
+            new_len: 0,
+            lines: Vec::with_capacity(end - start),
+        };
+        for &(edit, old_index, new_index) in &edits[start..end] {
+            match edit {
+                Edit::Equal => {
+                    hunk.old_len += 1;
+                    hunk.new_len += 1;
+                    hunk.lines.push((edit, old_index));
+                }
+                Edit::Delete => {
+                    hunk.old_len += 1;
+                    hunk.lines.push((edit, old_index));
+                }
+                Edit::Insert => {
+                    hunk.new_len += 1;
+                    hunk.lines.push((edit, new_index));
+                }
+            }
+        }
+        hunks.push(hunk);
+        change += 1;
+    }
+    hunks
+}
+
+/// Return the shortest edit script turning old into new, as a list of edits with the indices
+/// of the old and new lines they are at, using Myers' algorithm in linear space, so that
+/// files with many changes can be diffed without running out of memory. Deleted lines come
+/// before inserted lines, the way diff -u shows them.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<(Edit, usize, usize)> {
+    // Lines are compared by number, each different line having its own.
+    let mut numbers = HashMap::new();
+    let old_numbers = numbered(old, &mut numbers);
+    let new_numbers = numbered(new, &mut numbers);
+    // Lines that only one side has are always deleted or inserted, so they are left out of
+    // the search, which is quicker the fewer changes it has to find.
+    let old_kept = kept(&old_numbers, &new_numbers);
+    let new_kept = kept(&new_numbers, &old_numbers);
+    let a = old_kept
+        .iter()
+        .map(|&index| old_numbers[index])
+        .collect::<Vec<usize>>();
+    let b = new_kept
+        .iter()
+        .map(|&index| new_numbers[index])
+        .collect::<Vec<usize>>();
+    let mut equal = Vec::new();
+    equal_lines(&a, &b, 0, 0, &mut equal);
+
+    let mut edits = Vec::with_capacity(old.len() + new.len());
+    let (mut x, mut y) = (0, 0);
+    let ends = (old.len(), new.len());
+    for (old_index, new_index) in equal
+        .into_iter()
+        .map(|(x, y)| (old_kept[x], new_kept[y]))
+        .chain(std::iter::once(ends))
+    {
+        edits.extend((x..old_index).map(|x| (Edit::Delete, x, y)));
+        edits.extend((y..new_index).map(|y| (Edit::Insert, old_index, y)));
+        if (old_index, new_index) != ends {
+            edits.push((Edit::Equal, old_index, new_index));
+        }
+        (x, y) = (old_index + 1, new_index + 1);
+    }
+    edits
+}
+
+fn numbered<'l>(lines: &[&'l str], numbers: &mut HashMap<&'l str, usize>) -> Vec<usize> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = numbers.len();
+            *numbers.entry(*line).or_insert(next)
+        })
+        .collect()
+}
+
+/// The indices of the lines that the other lines have too.
+fn kept(lines: &[usize], other: &[usize]) -> Vec<usize> {
+    let other = other.iter().collect::<HashSet<&usize>>();
+    (0..lines.len())
+        .filter(|index| other.contains(&lines[*index]))
+        .collect()
+}
+
+/// Add the indices of the lines that are kept equal turning a into b, which start at x and y,
+/// by splitting them where a middle snake of the shortest edit script crosses them, and
+/// doing the same for the two halves.
+fn equal_lines(a: &[usize], b: &[usize], x: usize, y: usize, equal: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    equal.extend((0..prefix).map(|index| (x + index, y + index)));
+    let (a_middle, b_middle) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (x_middle, y_middle) = (x + prefix, y + prefix);
+    if let Some((split_x, split_y)) = middle_snake(a_middle, b_middle) {
+        equal_lines(
+            &a_middle[..split_x],
+            &b_middle[..split_y],
+            x_middle,
+            y_middle,
+            equal,
+        );
+        equal_lines(
+            &a_middle[split_x..],
+            &b_middle[split_y..],
+            x_middle + split_x,
+            y_middle + split_y,
+            equal,
+        );
+    }
+    equal.extend(
+        (0..suffix).map(|index| (x + a.len() - suffix + index, y + b.len() - suffix + index)),
+    );
+}
+
+/// Find where the forward and backward searches of Myers' algorithm meet, returning the point
+/// of a and b to split them at, or None when a and b have nothing in common. a and b neither
+/// start nor end with the same line.
+fn middle_snake(a: &[usize], b: &[usize]) -> Option<(usize, usize)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let b_lines = b.iter().collect::<HashSet<&usize>>();
+    if !a.iter().any(|line| b_lines.contains(line)) {
+        return None;
+    }
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let delta = n - m;
+    // Paths of the two searches can only meet on the same diagonal when they've taken steps
+    // of the same parity.
+    let forward_meets = delta % 2 != 0;
+    // Each search remembers only the furthest point it reached on each diagonal.
+    let mut forward = vec![-1isize; 2 * max_d as usize + 2];
+    let mut backward = vec![-1isize; 2 * max_d as usize + 2];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    // Diagonals that have run off the edges are trimmed from the ends of the searches.
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+    for d in 0..max_d {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && forward[index - 1] < forward[index + 1]) {
+                forward[index + 1]
+            } else {
+                forward[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index] = x;
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if forward_meets {
+                let other = offset + delta - k;
+                if reached(&backward, other).is_some_and(|backward_x| x >= n - backward_x) {
+                    return Some((x as usize, y as usize));
+                }
+            }
+        }
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && backward[index - 1] < backward[index + 1]) {
+                backward[index + 1]
+            } else {
+                backward[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[index] = x;
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !forward_meets {
+                let other = offset + delta - k;
+                if let Some(forward_x) =
+                    reached(&forward, other).filter(|forward_x| *forward_x >= n - x)
+                {
+                    return Some((forward_x as usize, (offset + forward_x - other) as usize));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// How far a search reached on the diagonal at the index, if it has reached it.
+fn reached(furthest: &[isize], index: isize) -> Option<isize> {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| furthest.get(index))
+        .copied()
+        .filter(|x| *x != -1)
+}
@@ -0,0 +1,228 @@
+//
+// ned, https://github.com/nevdelap/ned, encoding.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::{NedError, NedResult, StringError};
+use chardetng::EncodingDetector;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16BE_BOM: &[u8] = b"\xfe\xff";
+
+/// The --encoding that files without a byte order mark are read in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Auto,
+    Named(&'static encoding_rs::Encoding),
+}
+
+impl FromStr for Encoding {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Encoding::Auto);
+        }
+        match encoding_rs::Encoding::for_label(s.as_bytes()) {
+            Some(encoding) if encoding == encoding_rs::UTF_8 => Ok(Encoding::Utf8),
+            // UTF-16 files are told by their byte order marks.
+            Some(encoding) if encoding.output_encoding() == encoding => {
+                Ok(Encoding::Named(encoding))
+            }
+            Some(_) => Err(StringError {
+                err: format!("files cannot be written in encoding {}", s),
+            }),
+            None => Err(StringError {
+                err: format!("invalid encoding {}", s),
+            }),
+        }
+    }
+}
+
+/// The encoding of a file, told by its byte order mark, or given or detected with --encoding.
+/// Files are decoded for matching and written back in the encoding, and with the byte order
+/// mark, that they had. With --bytes they aren't decoded, and are written back as bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileEncoding {
+    Bytes,
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Legacy(&'static encoding_rs::Encoding),
+}
+
+impl FileEncoding {
+    /// The text in this encoding, with its byte order mark. Unless it is bytes, the text is
+    /// UTF-8, and it is an error for it to have characters that the encoding doesn't have.
+    pub fn encode<'t>(&self, text: &'t [u8]) -> NedResult<Cow<'t, [u8]>> {
+        Ok(match self {
+            FileEncoding::Bytes | FileEncoding::Utf8 => Cow::Borrowed(text),
+            FileEncoding::Utf8Bom => Cow::Owned([UTF8_BOM, text].concat()),
+            FileEncoding::Utf16Le => Cow::Owned(encode_utf16(
+                std::str::from_utf8(text)?,
+                UTF16LE_BOM,
+                u16::to_le_bytes,
+            )),
+            FileEncoding::Utf16Be => Cow::Owned(encode_utf16(
+                std::str::from_utf8(text)?,
+                UTF16BE_BOM,
+                u16::to_be_bytes,
+            )),
+            FileEncoding::Legacy(encoding) => {
+                let (bytes, _, unmappable) = encoding.encode(std::str::from_utf8(text)?);
+                if unmappable {
+                    return Err(NedError::from(format!(
+                        "has characters that cannot be written in {}, it was not changed",
+                        encoding.name()
+                    )));
+                }
+                Cow::Owned(bytes.into_owned())
+            }
+        })
+    }
+
+    /// The length of the byte order mark that the file starts with.
+    pub fn bom_len(&self) -> usize {
+        match self {
+            FileEncoding::Utf8Bom => UTF8_BOM.len(),
+            FileEncoding::Utf16Le | FileEncoding::Utf16Be => UTF16LE_BOM.len(),
+            FileEncoding::Bytes | FileEncoding::Utf8 | FileEncoding::Legacy(_) => 0,
+        }
+    }
+
+    /// The number of bytes that the text takes in the file, without its byte order mark.
+    /// Unless it is bytes, the text is UTF-8 decoded from the file.
+    pub fn encoded_len(&self, text: &[u8]) -> usize {
+        match self {
+            FileEncoding::Bytes | FileEncoding::Utf8 | FileEncoding::Utf8Bom => text.len(),
+            FileEncoding::Utf16Le | FileEncoding::Utf16Be => {
+                String::from_utf8_lossy(text).encode_utf16().count() * 2
+            }
+            FileEncoding::Legacy(encoding) => {
+                encoding.encode(&String::from_utf8_lossy(text)).0.len()
+            }
+        }
+    }
+}
+
+/// The text, with any invalid UTF-8 in it escaped as \xNN, so that it can be shown.
+pub fn escape_invalid_utf8(text: &[u8]) -> Cow<'_, str> {
+    if let Ok(text) = std::str::from_utf8(text) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for chunk in text.utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+pub fn has_utf16_bom(bytes: &[u8]) -> bool {
+    bytes.starts_with(UTF16LE_BOM) || bytes.starts_with(UTF16BE_BOM)
+}
+
+/// The length of the byte order mark of content that starts with head and will be decoded as
+/// UTF-8, so that it can be decoded a line at a time, or None if it will be decoded from
+/// another encoding.
+pub fn utf8_bom_len(head: &[u8], encoding: Encoding) -> Option<usize> {
+    if head.starts_with(UTF8_BOM) {
+        Some(UTF8_BOM.len())
+    } else if has_utf16_bom(head) || encoding != Encoding::Utf8 {
+        None
+    } else {
+        Some(0)
+    }
+}
+
+/// Decode the bytes, by their byte order mark if they have one, otherwise in the --encoding,
+/// returning the text without the byte order mark and the encoding it was in.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> NedResult<(Cow<'_, str>, FileEncoding)> {
+    if let Some(bytes) = bytes.strip_prefix(UTF8_BOM) {
+        Ok((
+            Cow::Borrowed(std::str::from_utf8(bytes)?),
+            FileEncoding::Utf8Bom,
+        ))
+    } else if let Some(bytes) = bytes.strip_prefix(UTF16LE_BOM) {
+        Ok((
+            Cow::Owned(decode_utf16(bytes, u16::from_le_bytes)?),
+            FileEncoding::Utf16Le,
+        ))
+    } else if let Some(bytes) = bytes.strip_prefix(UTF16BE_BOM) {
+        Ok((
+            Cow::Owned(decode_utf16(bytes, u16::from_be_bytes)?),
+            FileEncoding::Utf16Be,
+        ))
+    } else {
+        match encoding {
+            Encoding::Utf8 => Ok((
+                Cow::Borrowed(std::str::from_utf8(bytes)?),
+                FileEncoding::Utf8,
+            )),
+            Encoding::Auto => match std::str::from_utf8(bytes) {
+                Ok(text) => Ok((Cow::Borrowed(text), FileEncoding::Utf8)),
+                Err(_) => {
+                    let mut detector = EncodingDetector::new();
+                    detector.feed(bytes, true);
+                    decode_legacy(bytes, detector.guess(None, false))
+                }
+            },
+            Encoding::Named(encoding) => decode_legacy(bytes, encoding),
+        }
+    }
+}
+
+fn decode_legacy<'b>(
+    bytes: &'b [u8],
+    encoding: &'static encoding_rs::Encoding,
+) -> NedResult<(Cow<'b, str>, FileEncoding)> {
+    match encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => Ok((text, FileEncoding::Legacy(encoding))),
+        None => Err(NedError::from(format!(
+            "invalid {}: the content cannot be decoded",
+            encoding.name()
+        ))),
+    }
+}
+
+fn encode_utf16(text: &str, bom: &[u8], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    bom.iter()
+        .copied()
+        .chain(text.encode_utf16().flat_map(to_bytes))
+        .collect()
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> NedResult<String> {
+    let chunks = bytes.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(NedError::from(
+            "invalid utf-16: the content is an odd number of bytes".to_string(),
+        ));
+    }
+    let units = chunks
+        .map(|chunk| from_bytes([chunk[0], chunk[1]]))
+        .collect::<Vec<u16>>();
+    Ok(String::from_utf16(&units)?)
+}
@@ -0,0 +1,103 @@
+//
+// ned, https://github.com/nevdelap/ned, eol.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::StringError;
+use std::str::FromStr;
+
+/// The line endings that replaced files are given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Eol {
+    Lf,
+    Crlf,
+    Preserve,
+}
+
+impl FromStr for Eol {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Eol::Lf),
+            "crlf" => Ok(Eol::Crlf),
+            "preserve" => Ok(Eol::Preserve),
+            _ => Err(StringError {
+                err: format!("invalid eol option {}", s),
+            }),
+        }
+    }
+}
+
+impl Eol {
+    /// The replacement with its line endings made the file's. When preserving they are the
+    /// ones most of the text's lines end with, otherwise they are normalized afterwards.
+    pub fn adapt_replacement(&self, text: &[u8], replacement: &str) -> String {
+        if *self == Eol::Preserve && mostly_crlf(text) {
+            String::from_utf8(to_crlf(replacement.as_bytes()))
+                .expect("Bug, adding CRs keeps UTF-8 valid.")
+        } else {
+            replacement.to_string()
+        }
+    }
+
+    /// The text with all of its line endings made the chosen ones.
+    pub fn normalize(&self, text: Vec<u8>) -> Vec<u8> {
+        match self {
+            Eol::Lf => to_lf(&text),
+            Eol::Crlf => to_crlf(&text),
+            Eol::Preserve => text,
+        }
+    }
+}
+
+/// Whether any of the text's lines end with CRLF, in which case ^ and $ need to match
+/// around it.
+pub fn has_crlf(text: &[u8]) -> bool {
+    text.windows(2).any(|pair| pair == b"\r\n")
+}
+
+fn mostly_crlf(text: &[u8]) -> bool {
+    let crlfs = text.windows(2).filter(|pair| *pair == b"\r\n").count();
+    let lfs = text.iter().filter(|byte| **byte == b'\n').count() - crlfs;
+    crlfs > lfs
+}
+
+fn to_lf(text: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(text.len());
+    for (index, byte) in text.iter().enumerate() {
+        if *byte == b'\r' && text.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        result.push(*byte);
+    }
+    result
+}
+
+fn to_crlf(text: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(text.len());
+    let mut last = None;
+    for byte in text {
+        if *byte == b'\n' && last != Some(b'\r') {
+            result.push(b'\r');
+        }
+        result.push(*byte);
+        last = Some(*byte);
+    }
+    result
+}
@@ -18,32 +18,93 @@
 // 02110-1301, USA.
 //
 
-use crate::ned_error::stderr_write_err;
+use crate::ned_error::{stderr_write_err, StringError};
 use crate::parameters::Parameters;
+use crate::stats::Counter;
+use std::fs;
 use std::iter::IntoIterator;
 use std::path::Component;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use walkdir::{IntoIter, WalkDir};
 
+/// A --max-filesize, in bytes, or in KiB, MiB, or GiB with a K, M, or G suffix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileSize(pub u64);
+
+impl FromStr for FileSize {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, shift) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('K') => (&s[..s.len() - 1], 10),
+            Some('M') => (&s[..s.len() - 1], 20),
+            Some('G') => (&s[..s.len() - 1], 30),
+            _ => (s, 0),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(1 << shift))
+            .map(FileSize)
+            .ok_or_else(|| StringError {
+                err: format!("invalid file size {}", s),
+            })
+    }
+}
+
 pub struct Files {
     parameters: Parameters,
     walkdir: Box<IntoIter>,
+    // The root of the walk and the --backup-dir are canonicalized once, so that the
+    // directories walked can be compared with the --backup-dir without canonicalizing each.
+    root: PathBuf,
+    canonical_root: Option<PathBuf>,
+    backup_dir: Option<PathBuf>,
 }
 
 impl Files {
     pub fn new(parameters: &Parameters, glob: &str) -> Files {
-        let mut walkdir = WalkDir::new(&glob)
+        let mut walkdir = WalkDir::new(glob)
             .follow_links(parameters.follow)
             .sort_by(|a, b| a.file_name().cmp(b.file_name()));
         if !parameters.recursive {
             walkdir = walkdir.max_depth(1);
         }
+        let backup_dir = parameters
+            .backup_dir
+            .as_ref()
+            .and_then(|backup_dir| canonicalize_missing(backup_dir));
         Files {
             parameters: parameters.clone(),
             walkdir: Box::new(walkdir.into_iter()),
+            root: PathBuf::from(glob),
+            canonical_root: backup_dir
+                .as_ref()
+                .and_then(|_| fs::canonicalize(glob).ok()),
+            backup_dir,
         }
     }
 
+    /// Whether the directory is the --backup-dir. Without --follow the directories are all
+    /// under the root as it is, otherwise they may be reached through symlinks.
+    fn is_backup_dir(&self, path: &Path) -> bool {
+        let backup_dir = match self.backup_dir {
+            Some(ref backup_dir) => backup_dir,
+            None => return false,
+        };
+        let path = match (&self.canonical_root, path.strip_prefix(&self.root)) {
+            (Some(canonical_root), Ok(relative)) if !self.parameters.follow => {
+                canonical_root.join(relative)
+            }
+            _ => match fs::canonicalize(path) {
+                Ok(path) => path,
+                Err(_) => return false,
+            },
+        };
+        path == *backup_dir
+    }
+
     /// Normalize relative paths (remove ./ and normalize ../) without
     /// converting symlinks to the path they point to.
     fn normalize_relative_paths(input_path: PathBuf) -> std::io::Result<PathBuf> {
@@ -79,6 +140,28 @@ impl Files {
     }
 }
 
+/// The canonical path of path, which may not exist yet, such as a --backup-dir that is created
+/// when the first file is backed up, from the nearest of its ancestors that does.
+fn canonicalize_missing(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return Some(
+                missing
+                    .iter()
+                    .rev()
+                    .fold(canonical, |path, name| path.join(name)),
+            );
+        }
+        missing.push(existing.file_name()?);
+        existing = match existing.parent()? {
+            parent if parent.as_os_str().is_empty() => Path::new("."),
+            parent => parent,
+        };
+    }
+}
+
 impl Iterator for Files {
     type Item = Box<PathBuf>;
 
@@ -98,7 +181,8 @@ impl Iterator for Files {
                                             .parameters
                                             .exclude_dirs
                                             .iter()
-                                            .any(|pattern| pattern.matches(file_name));
+                                            .any(|pattern| pattern.matches(file_name))
+                                        || self.is_backup_dir(entry.path());
                                     if excluded_dir {
                                         self.walkdir.skip_current_dir();
                                     }
@@ -112,11 +196,24 @@ impl Iterator for Files {
                                             .iter()
                                             .any(|pattern| pattern.matches(file_name)));
                                 let excluded_file = file_type.is_file()
-                                    && self
+                                    && (self
                                         .parameters
                                         .excludes
                                         .iter()
-                                        .any(|pattern| pattern.matches(file_name));
+                                        .any(|pattern| pattern.matches(file_name))
+                                        || self.parameters.backup.as_ref().is_some_and(|backup| {
+                                            backup.is_backup_file_name(file_name)
+                                        }));
+                                if let Some(ref stats) = self.parameters.stats {
+                                    if file_type.is_file() {
+                                        stats.add(Counter::FilesWalked, 1);
+                                        if !included_file || excluded_file {
+                                            stats.add(Counter::SkippedExcluded, 1);
+                                        } else if !all && hidden {
+                                            stats.add(Counter::SkippedHidden, 1);
+                                        }
+                                    }
+                                }
                                 if included_file && !excluded_file && (all || !hidden) {
                                     return Some(Box::new(
                                         Self::normalize_relative_paths(entry.path().to_path_buf())
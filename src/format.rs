@@ -0,0 +1,188 @@
+//
+// ned, https://github.com/nevdelap/ned, format.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::encoding::escape_invalid_utf8;
+use crate::ned_error::{NedResult, StringError};
+use regex::bytes::{Captures, Regex};
+use std::io::Write;
+use std::str::FromStr;
+
+/// A --format template, shown for each match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Path,
+    Line,
+    Column,
+    ByteOffset,
+    Match,
+    LineText,
+    Group(usize),
+    Name(String),
+}
+
+/// What the placeholders of a --format template are filled in with, for a match, or for a line
+/// without one with -v/--no-match, when there are no captures.
+pub struct Fields<'a> {
+    pub path: &'a str,
+    pub line_number: usize,
+    pub column: usize,
+    pub byte_offset: usize,
+    pub line_text: &'a [u8],
+    pub captures: Option<Captures<'a>>,
+}
+
+impl FromStr for Format {
+    type Err = StringError;
+
+    /// {path}, {line}, {column}, {byte_offset}, {match}, {line_text}, and {N} or {NAME} for
+    /// groups are placeholders, {{ and }} are braces, and \t, \n, \r, \0, and \\ are escapes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || StringError {
+            err: format!("invalid format option {}", s),
+        };
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(char) = chars.next() {
+            match char {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(char) if char.is_alphanumeric() || char == '_' => name.push(char),
+                            _ => return Err(err()),
+                        }
+                    }
+                    let piece = match name.as_str() {
+                        "path" => Piece::Path,
+                        "line" => Piece::Line,
+                        "column" => Piece::Column,
+                        "byte_offset" => Piece::ByteOffset,
+                        "match" => Piece::Match,
+                        "line_text" => Piece::LineText,
+                        "" => return Err(err()),
+                        name => match name.parse::<usize>() {
+                            Ok(index) => Piece::Group(index),
+                            Err(_) => Piece::Name(name.to_string()),
+                        },
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(piece);
+                }
+                '\\' => {
+                    // Unrecognised escapes, and a backslash at the end, are unchanged.
+                    let escape = match chars.peek() {
+                        Some('t') => Some('\t'),
+                        Some('n') => Some('\n'),
+                        Some('r') => Some('\r'),
+                        Some('0') => Some('\0'),
+                        Some('\\') => Some('\\'),
+                        _ => None,
+                    };
+                    match escape {
+                        Some(escape) => {
+                            chars.next();
+                            text.push(escape);
+                        }
+                        None => text.push('\\'),
+                    }
+                }
+                char => text.push(char),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Format { pieces })
+    }
+}
+
+impl Format {
+    /// Check that the groups that the template has placeholders for are in the pattern, so
+    /// that a misspelled placeholder isn't quietly empty.
+    pub fn check_groups(&self, re: &Regex) -> Result<(), StringError> {
+        for piece in &self.pieces {
+            let name = match piece {
+                Piece::Group(index) if *index >= re.captures_len() => index.to_string(),
+                Piece::Name(name) if !re.capture_names().any(|group| group == Some(name)) => {
+                    name.clone()
+                }
+                _ => continue,
+            };
+            return Err(StringError {
+                err: format!(
+                    "invalid format option, {{{}}} is neither a placeholder nor a group",
+                    name
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Write the template filled in with the fields, and a newline. Groups that didn't
+    /// participate in the match are empty.
+    pub fn write(&self, output: &mut dyn Write, fields: &Fields) -> NedResult<()> {
+        let mut line = String::new();
+        for piece in &self.pieces {
+            let group = |get: Option<regex::bytes::Match>| {
+                get.map_or(String::new(), |group| {
+                    escape_invalid_utf8(group.as_bytes()).into_owned()
+                })
+            };
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Path => line.push_str(fields.path),
+                Piece::Line => line.push_str(&fields.line_number.to_string()),
+                Piece::Column => line.push_str(&fields.column.to_string()),
+                Piece::ByteOffset => line.push_str(&fields.byte_offset.to_string()),
+                Piece::LineText => line.push_str(&escape_invalid_utf8(fields.line_text)),
+                Piece::Match => {
+                    line.push_str(&group(fields.captures.as_ref().and_then(|c| c.get(0))))
+                }
+                Piece::Group(index) => {
+                    line.push_str(&group(fields.captures.as_ref().and_then(|c| c.get(*index))))
+                }
+                Piece::Name(name) => {
+                    line.push_str(&group(fields.captures.as_ref().and_then(|c| c.name(name))))
+                }
+            }
+        }
+        line.push('\n');
+        output.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//
+// ned, https://github.com/nevdelap/ned, highlight.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use ansi_term::Colour::Red;
+use ansi_term::Style;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// What a span of highlighted text is, which decides its style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Highlight {
+    Match,
+    Group,
+    Replacement,
+}
+
+impl Highlight {
+    pub fn style(self) -> Style {
+        match self {
+            Highlight::Match | Highlight::Replacement => Red.bold(),
+            Highlight::Group => Red.bold().underline(),
+        }
+    }
+}
+
+/// A span of the text, and how it is highlighted.
+pub type Segment = (Range<usize>, Highlight);
+
+/// The segments of a match, being the spans of its groups, and the rest of it between them.
+/// A group inside of, or overlapping, one that starts before it is part of that one, and empty
+/// groups have nothing to highlight.
+pub fn match_segments(
+    span: &Range<usize>,
+    groups: impl Iterator<Item = Range<usize>>,
+) -> Vec<Segment> {
+    let mut groups = groups
+        .filter(|group| !group.is_empty())
+        .collect::<Vec<Range<usize>>>();
+    groups.sort_by_key(|group| group.start);
+    let mut segments = Vec::with_capacity(2 * groups.len() + 1);
+    let mut last_end = span.start;
+    for group in groups {
+        if group.start < last_end {
+            continue;
+        }
+        if group.start > last_end {
+            segments.push((last_end..group.start, Highlight::Match));
+        }
+        last_end = group.end;
+        segments.push((group, Highlight::Group));
+    }
+    if last_end < span.end || segments.is_empty() {
+        segments.push((last_end..span.end, Highlight::Match));
+    }
+    segments
+}
+
+/// The part of the text in range, with the segments in it, which are in the order they are in
+/// the text, in their styles.
+pub fn paint<'t>(text: &'t [u8], range: Range<usize>, segments: &[Segment]) -> Cow<'t, [u8]> {
+    if segments.is_empty() {
+        return Cow::Borrowed(&text[range]);
+    }
+    let mut painted = Vec::with_capacity(range.len() + 16 * segments.len());
+    let mut last_end = range.start;
+    for (span, highlight) in segments {
+        let style = highlight.style();
+        painted.extend_from_slice(&text[last_end..span.start]);
+        painted.extend_from_slice(style.prefix().to_string().as_bytes());
+        painted.extend_from_slice(&text[span.clone()]);
+        painted.extend_from_slice(style.suffix().to_string().as_bytes());
+        last_end = span.end;
+    }
+    painted.extend_from_slice(&text[last_end..range.end]);
+    Cow::Owned(painted)
+}
@@ -0,0 +1,173 @@
+//
+// ned, https://github.com/nevdelap/ned, interactive.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::encoding::escape_invalid_utf8;
+use crate::ned_error::NedResult;
+use crate::parameters::Parameters;
+use ansi_term::Colour::Purple;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// State for --interactive, which is shared by clones so that quitting in one file stops
+/// the whole run.
+#[derive(Clone, Debug, Default)]
+pub struct Interactive {
+    quit: Arc<AtomicBool>,
+}
+
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+impl Interactive {
+    pub fn has_quit(&self) -> bool {
+        self.quit.load(Ordering::Relaxed)
+    }
+
+    /// Show each of the matches in text, with the lines around it and the replacement it
+    /// would get, and ask whether to replace it, returning the spans of the ones that are
+    /// accepted. Answering all accepts the rest of the file's matches without asking,
+    /// answering quit rejects them and stops the run.
+    pub fn confirm<'c>(
+        &self,
+        parameters: &Parameters,
+        file_name: &Option<String>,
+        text: &[u8],
+        replacements: &'c [(Range<usize>, Vec<u8>)],
+        prompt: &mut dyn Write,
+        answers: &mut dyn BufRead,
+    ) -> NedResult<Vec<&'c Range<usize>>> {
+        let mut accepted = Vec::new();
+        let mut all = false;
+        for (span, replacement) in replacements {
+            if self.has_quit() {
+                break;
+            }
+            if !all {
+                write_match(
+                    prompt,
+                    parameters,
+                    file_name,
+                    text,
+                    span.start,
+                    span.end,
+                    replacement,
+                )?;
+                match ask(prompt, answers)? {
+                    Answer::Yes => {}
+                    Answer::No => continue,
+                    Answer::All => all = true,
+                    Answer::Quit => {
+                        self.quit.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+            accepted.push(span);
+        }
+        Ok(accepted)
+    }
+}
+
+fn ask(prompt: &mut dyn Write, answers: &mut dyn BufRead) -> NedResult<Answer> {
+    loop {
+        prompt.write_all(b"Replace? (y)es, (n)o, (a)ll in file, (q)uit: ")?;
+        prompt.flush()?;
+        let mut answer = String::new();
+        if answers.read_line(&mut answer)? == 0 {
+            // Nobody to answer.
+            prompt.write_all(b"\n")?;
+            return Ok(Answer::Quit);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Answer::Yes),
+            "n" | "no" => return Ok(Answer::No),
+            "a" | "all" => return Ok(Answer::All),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Write the file name and line number of the match, and the lines around it with the match
+/// and its replacement in brackets. The replacement is already colored when colors are on.
+fn write_match(
+    prompt: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &Option<String>,
+    text: &[u8],
+    start: usize,
+    end: usize,
+    replacement: &[u8],
+) -> NedResult<()> {
+    let line_start = |index: usize| {
+        text[..index]
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |index| index + 1)
+    };
+    let line_end = |index: usize| {
+        text[index..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(text.len(), |position| index + position)
+    };
+    let line_number = text[..start].iter().filter(|byte| **byte == b'\n').count() + 1;
+    let mut context_start = line_start(start);
+    for _ in 0..parameters.change_context {
+        if context_start == 0 {
+            break;
+        }
+        context_start = line_start(context_start - 1);
+    }
+    let mut context_end = line_end(end);
+    for _ in 0..parameters.change_context {
+        // Stop at the end of the text, or at its final newline.
+        if context_end + 1 >= text.len() {
+            break;
+        }
+        context_end = line_end(context_end + 1);
+    }
+
+    let mut location = match file_name {
+        Some(file_name) => format!("{}:{}:", file_name, line_number),
+        None => format!("{}:", line_number),
+    };
+    if parameters.colors {
+        location = Purple.paint(location).to_string();
+    }
+    prompt.write_all(
+        format!(
+            "{}\n{}[{} -> {}]{}\n",
+            location,
+            escape_invalid_utf8(&text[context_start..start]),
+            escape_invalid_utf8(&text[start..end]),
+            escape_invalid_utf8(replacement),
+            escape_invalid_utf8(&text[end..context_end])
+        )
+        .as_bytes(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//
+// ned, https://github.com/nevdelap/ned, journal.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::checksum::checksum;
+use crate::ned_error::{stderr_write_file_err, NedError, NedResult, StringError};
+use crate::rewrite::rewrite_file;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+static MANIFEST: &str = "manifest";
+
+/// The journal of one run of ned. Each run has its own directory in the journal directory,
+/// named by its run id, holding a manifest with a line for each file that was replaced in,
+/// giving the number of the entry, the checksum of the content that ned wrote, and the file's
+/// path, and a file for each entry, named by its number, holding the file's original content.
+#[derive(Clone, Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    run_id: String,
+    // The number of entries recorded, shared so that clones record into the same run.
+    entries: Arc<Mutex<usize>>,
+}
+
+impl Journal {
+    pub fn new(dir: PathBuf) -> Journal {
+        let now = OffsetDateTime::now_utc();
+        // Sortable, so that the last run is the greatest.
+        let run_id = format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}.{:09}",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second(),
+            now.nanosecond()
+        );
+        Journal {
+            dir,
+            run_id,
+            entries: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// The journal directory used when --journal is given without a DIR.
+    pub fn default_dir() -> NedResult<PathBuf> {
+        let home = env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .ok_or_else(|| {
+                NedError::from("cannot find the home directory for the journal".to_string())
+            })?;
+        Ok(PathBuf::from(home)
+            .join(".local")
+            .join("state")
+            .join("ned")
+            .join("journal"))
+    }
+
+    /// Record the original content of the file at path, and the checksum of the content it is
+    /// about to be given. This is done before the file is written so that it can always be
+    /// undone.
+    pub fn record(&self, path: &Path, original: &[u8], new: &[u8]) -> NedResult<()> {
+        let path = fs::canonicalize(path)?;
+        let run_dir = self.dir.join(&self.run_id);
+        let mut entries = self.entries.lock().expect("Bug, journal lock poisoned.");
+        fs::create_dir_all(&run_dir)?;
+        let entry = *entries + 1;
+        fs::write(run_dir.join(entry.to_string()), original)?;
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(run_dir.join(MANIFEST))?;
+        manifest.write_all(
+            format!("{}\t{}\t{}\n", entry, checksum(new), escape_path(&path)).as_bytes(),
+        )?;
+        manifest.sync_all()?;
+        *entries = entry;
+        Ok(())
+    }
+
+    /// Restore the files replaced in by the run with the given id, or by the last run if it is
+    /// empty, writing the path of each restored file to output. Files are restored newest
+    /// first, so that a file replaced in more than once is restored to what it was before the
+    /// run. Files whose content is no longer what ned wrote are not restored. The run is
+    /// removed once all of its files have been restored, returns whether they were.
+    pub fn undo(&self, output: &mut dyn Write, run_id: &str) -> NedResult<bool> {
+        let run_dir = if run_id.is_empty() {
+            self.last_run_dir()?
+        } else {
+            self.dir.join(run_id)
+        };
+        let manifest = fs::read_to_string(run_dir.join(MANIFEST)).map_err(|_| {
+            NedError::from(format!(
+                "there is no journaled run {} in {}",
+                run_id,
+                self.dir.to_string_lossy()
+            ))
+        })?;
+        let mut all_restored = true;
+        for line in manifest.lines().rev() {
+            let mut fields = line.splitn(3, '\t');
+            let (entry, new_checksum, path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(entry), Some(new_checksum), Some(path)) => {
+                    (entry, new_checksum, unescape_path(path))
+                }
+                _ => {
+                    return Err(NedError::from(format!(
+                        "the journal {} is damaged",
+                        run_dir.to_string_lossy()
+                    )))
+                }
+            };
+            let entry_path = run_dir.join(entry);
+            if !entry_path.exists() {
+                // Restored by an earlier undo.
+                continue;
+            }
+            match restore_file(&path, &entry_path, new_checksum) {
+                Ok(()) => {
+                    fs::remove_file(&entry_path)?;
+                    output.write_all(format!("{}\n", path.to_string_lossy()).as_bytes())?;
+                }
+                Err(err) => {
+                    stderr_write_file_err(&path, &err);
+                    all_restored = false;
+                }
+            }
+        }
+        if all_restored {
+            fs::remove_dir_all(&run_dir)?;
+        }
+        Ok(all_restored)
+    }
+
+    fn last_run_dir(&self) -> NedResult<PathBuf> {
+        let mut run_ids = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir
+                .flatten()
+                .filter(|entry| entry.path().join(MANIFEST).is_file())
+                .map(|entry| entry.file_name())
+                .collect::<Vec<OsString>>(),
+            Err(_) => vec![],
+        };
+        run_ids.sort();
+        match run_ids.pop() {
+            Some(run_id) => Ok(self.dir.join(run_id)),
+            None => Err(NedError::from(format!(
+                "there are no journaled runs in {}",
+                self.dir.to_string_lossy()
+            ))),
+        }
+    }
+}
+
+fn restore_file(path: &Path, entry_path: &Path, new_checksum: &str) -> NedResult<()> {
+    let original = fs::read(entry_path)?;
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    if content == original {
+        return Ok(());
+    }
+    if checksum(&content) != new_checksum {
+        return Err(NedError::ParameterError(StringError {
+            err: "has changed since it was replaced, it was not restored".to_string(),
+        }));
+    }
+    for warning in rewrite_file(path, &mut file, &original, false)? {
+        stderr_write_file_err(path, &warning);
+    }
+    Ok(())
+}
+
+/// Paths are written to the manifest with control characters, %, and non-ASCII bytes
+/// percent encoded, so that any path fits on its line, and comes back as it was.
+fn escape_path(path: &Path) -> String {
+    let mut escaped = String::new();
+    for byte in path_bytes(path) {
+        if byte < 0x20 || byte == b'%' || byte >= 0x7f {
+            escaped.push_str(&format!("%{:02X}", byte));
+        } else {
+            escaped.push(byte as char);
+        }
+    }
+    escaped
+}
+
+fn unescape_path(escaped: &str) -> PathBuf {
+    let escaped = escaped.as_bytes();
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut index = 0;
+    while index < escaped.len() {
+        if escaped[index] == b'%' {
+            if let Some(byte) = escaped
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        bytes.push(escaped[index]);
+        index += 1;
+    }
+    path_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).to_string())
+}
@@ -0,0 +1,242 @@
+//
+// ned, https://github.com/nevdelap/ned, json.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::journal::path_bytes;
+use crate::ned_error::NedResult;
+use crate::position::{column, ColumnUnit};
+use crate::stats::Stats;
+use regex::bytes::Regex;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A JSON object, built a field at a time from values that are already JSON.
+pub struct Object {
+    fields: Vec<String>,
+}
+
+impl Object {
+    pub fn new() -> Object {
+        Object { fields: Vec::new() }
+    }
+
+    /// An --json event of the type.
+    pub fn event(event_type: &str) -> Object {
+        Object::new().field("type", string(event_type.as_bytes()))
+    }
+
+    pub fn field(mut self, name: &str, value: String) -> Object {
+        self.fields.push(format!("\"{}\":{}", name, value));
+        self
+    }
+
+    /// Write the object on a line of its own.
+    pub fn write(self, output: &mut dyn Write) -> NedResult<()> {
+        output.write_all(format!("{}\n", self).as_bytes())?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{{{}}}", self.fields.join(","))
+    }
+}
+
+/// The bytes as a JSON string if they are UTF-8, otherwise as an object holding them in base64,
+/// {"bytes":"..."}, so that what isn't UTF-8 can be had back as it was.
+pub fn string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => escape(text),
+        Err(_) => format!("{{\"bytes\":\"{}\"}}", base64(bytes)),
+    }
+}
+
+/// The path as a JSON string, or as base64 bytes if it isn't UTF-8.
+pub fn path(path: &Path) -> String {
+    string(&path_bytes(path))
+}
+
+pub fn option(value: Option<usize>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+/// Write the begin event of a file, which comes before its other events.
+pub fn write_begin(output: &mut dyn Write, path: &str) -> NedResult<()> {
+    Object::event("begin")
+        .field("path", path.to_string())
+        .write(output)
+}
+
+/// Write the end event of a file, which comes after its other events.
+pub fn write_end(output: &mut dyn Write, path: &str, found_matches: bool) -> NedResult<()> {
+    Object::event("end")
+        .field("path", path.to_string())
+        .field("found_matches", found_matches.to_string())
+        .write(output)
+}
+
+/// Write the event of a binary file that has matches.
+pub fn write_binary(output: &mut dyn Write, path: &str) -> NedResult<()> {
+    Object::event("binary")
+        .field("path", path.to_string())
+        .write(output)
+}
+
+/// Write the summary event, with the --stats of the run, which comes after all other events.
+pub fn write_summary(output: &mut dyn Write, stats: &Stats) -> NedResult<()> {
+    Object::event("summary")
+        .field("stats", stats.to_json())
+        .write(output)
+}
+
+/// Write the match event of a line, or of a whole file when there is no line number, with
+/// each of the matches at the spans, their offsets in the text and their column on their line,
+/// and their groups.
+pub fn write_match<'s>(
+    output: &mut dyn Write,
+    re: &Regex,
+    path: &str,
+    line_number: Option<usize>,
+    byte_offset: usize,
+    text: &[u8],
+    spans: impl Iterator<Item = &'s Range<usize>>,
+) -> NedResult<()> {
+    let names = re.capture_names().collect::<Vec<Option<&str>>>();
+    let submatches = spans
+        .map(|span| {
+            let captures = re
+                .captures_at(text, span.start)
+                .expect("Bug, the span is where a match is.");
+            let groups = captures
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter_map(|(index, group)| {
+                    group.map(|group| {
+                        Object::new()
+                            .field("index", index.to_string())
+                            .field(
+                                "name",
+                                names[index]
+                                    .map_or("null".to_string(), |name| string(name.as_bytes())),
+                            )
+                            .field("start", group.start().to_string())
+                            .field("end", group.end().to_string())
+                            .field("text", string(group.as_bytes()))
+                            .to_string()
+                    })
+                })
+                .collect::<Vec<String>>();
+            Object::new()
+                .field("start", span.start.to_string())
+                .field("end", span.end.to_string())
+                .field(
+                    "column",
+                    column(text, span.start, ColumnUnit::Chars).to_string(),
+                )
+                .field("text", string(&text[span.clone()]))
+                .field("groups", format!("[{}]", groups.join(",")))
+                .to_string()
+        })
+        .collect::<Vec<String>>();
+    Object::event("match")
+        .field("path", path.to_string())
+        .field("line_number", option(line_number))
+        .field("byte_offset", byte_offset.to_string())
+        .field("text", string(text))
+        .field("matches", format!("[{}]", submatches.join(",")))
+        .write(output)
+}
+
+/// Write the context event of a line shown around a match.
+pub fn write_context(
+    output: &mut dyn Write,
+    path: &str,
+    line_number: Option<usize>,
+    byte_offset: usize,
+    text: &[u8],
+) -> NedResult<()> {
+    Object::event("context")
+        .field("path", path.to_string())
+        .field("line_number", option(line_number))
+        .field("byte_offset", byte_offset.to_string())
+        .field("text", string(text))
+        .write(output)
+}
+
+/// Write the replace event of the text at the span of the file, which starts on the line.
+pub fn write_replace(
+    output: &mut dyn Write,
+    path: &str,
+    line_number: usize,
+    span: Range<usize>,
+    old: &[u8],
+    new: &[u8],
+) -> NedResult<()> {
+    Object::event("replace")
+        .field("path", path.to_string())
+        .field("line_number", line_number.to_string())
+        .field("start", span.start.to_string())
+        .field("end", span.end.to_string())
+        .field("old", string(old))
+        .field("new", string(new))
+        .write(output)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if char < ' ' => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+            char => escaped.push(char),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |triple, (index, byte)| {
+                triple | (*byte as u32) << (16 - 8 * index)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[(triple >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
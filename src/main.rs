@@ -26,32 +26,69 @@ extern crate regex;
 extern crate time;
 extern crate walkdir;
 
+mod backup;
+mod binary;
+mod checksum;
 mod colors;
+mod diff;
+mod encoding;
+mod eol;
 mod files;
+mod format;
+mod highlight;
+mod interactive;
+mod journal;
+mod json;
+mod matches;
+mod mmap;
 mod ned_error;
 mod options_with_defaults;
 mod opts;
 mod parameters;
+mod position;
+mod rewrite;
+mod snapshot;
 mod source;
+mod stats;
 #[cfg(test)]
 mod tests;
 
-use crate::files::Files;
+use crate::backup::backup_file;
+use crate::binary::{is_binary, Binary, DETECTION_LEN};
+use crate::diff::write_unified_diff;
+use crate::encoding::{decode, escape_invalid_utf8, utf8_bom_len, FileEncoding};
+use crate::eol::has_crlf;
+use crate::files::{FileSize, Files};
+use crate::format::Fields;
+use crate::highlight::{match_segments, paint, Highlight, Segment};
+use crate::interactive::Interactive;
+use crate::matches::Matches;
+use crate::mmap::map_file;
 use crate::ned_error::{stderr_write_file_err, NedError, NedResult};
 use crate::options_with_defaults::OptionsWithDefaults;
 use crate::opts::{make_opts, usage_brief, usage_full, usage_version};
 use crate::parameters::{get_parameters, Parameters};
+use crate::position::{ColumnUnit, FileOffset, Locator, Position};
+use crate::rewrite::{lock_file, rewrite_file};
+use crate::snapshot::Snapshot;
 use crate::source::Source;
+use crate::stats::Counter;
 #[cfg(target_os = "windows")]
 use ansi_term::enable_ansi_support;
-use ansi_term::Colour::{Purple, Red};
-use regex::{Captures, Match, Regex};
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::{stderr, stdin, stdout, Read, Seek, SeekFrom, Write};
-use std::iter::Iterator;
+use ansi_term::Colour::Purple;
+use regex::bytes::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{stderr, stdin, stdout, BufRead, BufReader, Cursor, ErrorKind, Read, Write};
+use std::io::{Seek, SeekFrom};
+use std::iter::{self, Iterator};
+use std::ops::Range;
+use std::path::Path;
 use std::string::String;
-use std::{env, process};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::{env, process, thread};
 
 fn main() {
     // Output is passed here so that tests can
@@ -61,8 +98,7 @@ fn main() {
     let exit_code = match ned(&mut output, &env::args().skip(1).collect::<Vec<String>>()) {
         Ok(exit_code) => exit_code,
         Err(err) => {
-            let _ = stderr()
-                .write_all(&format!("{}\n{}\n\n", usage_brief(), err.to_string()).into_bytes());
+            let _ = stderr().write_all(&format!("{}\n{}\n\n", usage_brief(), err).into_bytes());
             1
         }
     };
@@ -86,6 +122,15 @@ fn ned(output: &mut dyn Write, args: &[String]) -> NedResult<i32> {
         process::exit(0);
     }
 
+    if let Some(ref run_id) = parameters.undo {
+        let journal = parameters
+            .journal
+            .as_ref()
+            .expect("Bug, --undo always has a journal.");
+        let all_restored = journal.undo(output, run_id)?;
+        return Ok(if all_restored { 0 } else { 1 });
+    }
+
     if parameters.regex.is_none() {
         let _ = stderr().write_all(&format!("\n{}\n\n", usage_brief()).into_bytes());
         process::exit(1);
@@ -107,6 +152,12 @@ fn ned(output: &mut dyn Write, args: &[String]) -> NedResult<i32> {
     }
 
     let found_matches = process_files(output, &parameters)?;
+    if let Some(ref stats) = parameters.stats {
+        if parameters.json && !parameters.quiet {
+            json::write_summary(output, stats)?;
+        }
+        stats.write(&mut stderr())?;
+    }
     Ok(if found_matches { 0 } else { 1 })
 }
 
@@ -114,32 +165,25 @@ fn process_files(output: &mut dyn Write, parameters: &Parameters) -> NedResult<b
     let mut found_matches = false;
     if parameters.stdin {
         let mut source = Source::Stdin(Box::new(stdin()));
-        found_matches = process_file(output, parameters, &None, &mut source)?;
+        found_matches = process_source(output, parameters, &None, &mut source)?;
     } else {
+        // --interactive asks about one file at a time.
+        let parallel = parameters.threads > 1 && parameters.interactive.is_none();
         for glob in &parameters.globs {
-            for path_buf in &mut Files::new(parameters, glob) {
-                match OpenOptions::new()
-                    .read(true)
-                    .write(parameters.replace.is_some())
-                    .open(path_buf.as_path())
-                {
-                    Ok(file) => {
-                        let mut source = Source::File(Box::new(file));
-                        let file_name = &Some(path_buf.as_path().to_string_lossy().to_string());
-                        found_matches |=
-                            match process_file(output, parameters, file_name, &mut source) {
-                                Ok(found_matches) => found_matches,
-                                Err(err) => {
-                                    if err.io_error_kind() == Some(std::io::ErrorKind::BrokenPipe) {
-                                        break;
-                                    }
-                                    stderr_write_file_err(&path_buf, &err);
-                                    false
-                                }
-                            }
-                    }
-                    Err(err) => stderr_write_file_err(&path_buf, &err),
-                }
+            let files = Files::new(parameters, glob);
+            let (glob_found_matches, broken_pipe) = if parallel {
+                process_files_in_parallel(output, parameters, files)
+            } else {
+                process_files_in_order(output, parameters, files)
+            };
+            found_matches |= glob_found_matches;
+            if broken_pipe
+                || parameters
+                    .interactive
+                    .as_ref()
+                    .is_some_and(Interactive::has_quit)
+            {
+                break;
             }
             if parameters.quiet && found_matches {
                 break;
@@ -151,229 +195,1142 @@ fn process_files(output: &mut dyn Write, parameters: &Parameters) -> NedResult<b
     Ok(found_matches)
 }
 
+/// Process the files one after the other, returning whether any matched, and whether output
+/// stopped because its pipe was closed.
+fn process_files_in_order(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    files: Files,
+) -> (bool, bool) {
+    let mut found_matches = false;
+    for path_buf in files {
+        match process_path(output, parameters, &path_buf) {
+            Ok(file_found_matches) => found_matches |= file_found_matches,
+            Err(err) => {
+                if err.io_error_kind() == Some(ErrorKind::BrokenPipe) {
+                    return (found_matches, true);
+                }
+                stderr_write_file_err(&path_buf, &err);
+            }
+        }
+        if parameters
+            .interactive
+            .as_ref()
+            .is_some_and(Interactive::has_quit)
+        {
+            break;
+        }
+    }
+    (found_matches, false)
+}
+
+/// Process the files on --threads workers, each of which takes the next file when it is done
+/// with the last, so that each file is only read and written by one of them. The output of
+/// each file is held until the output of the files before it has been written, so that it is
+/// in the same order as if the files were processed one after the other.
+fn process_files_in_parallel(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    files: Files,
+) -> (bool, bool) {
+    let files = Mutex::new(files.enumerate());
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..parameters.threads {
+            let sender = sender.clone();
+            let (files, stop) = (&files, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let next = files.lock().expect("Bug, files lock poisoned.").next();
+                    let Some((index, path_buf)) = next else {
+                        break;
+                    };
+                    let mut file_output = Vec::new();
+                    let result = process_path(&mut file_output, parameters, &path_buf);
+                    if sender.send((index, path_buf, file_output, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut found_matches = false;
+        let mut broken_pipe = false;
+        let mut done = BTreeMap::new();
+        let mut next_index = 0;
+        for (index, path_buf, file_output, result) in receiver {
+            let _ = done.insert(index, (path_buf, file_output, result));
+            while let Some((path_buf, file_output, result)) = done.remove(&next_index) {
+                next_index += 1;
+                if broken_pipe {
+                    continue;
+                }
+                // What the file wrote before any error it had is shown before the error.
+                match output
+                    .write_all(&file_output)
+                    .map_err(NedError::from)
+                    .and(result)
+                {
+                    Ok(file_found_matches) => found_matches |= file_found_matches,
+                    Err(err) => {
+                        if err.io_error_kind() == Some(ErrorKind::BrokenPipe) {
+                            broken_pipe = true;
+                            stop.store(true, Ordering::Relaxed);
+                        } else {
+                            stderr_write_file_err(&path_buf, &err);
+                        }
+                    }
+                }
+            }
+        }
+        (found_matches, broken_pipe)
+    })
+}
+
+/// Open the file at the path, for writing too when replacing in it, and process it. With --lock the
+/// file is locked while it is replaced in, and if it was replaced by another process while
+/// waiting for the lock, the file that replaced it is opened instead.
+fn process_path(output: &mut dyn Write, parameters: &Parameters, path: &Path) -> NedResult<bool> {
+    // Files that are too big are skipped before they are opened, or waited on for a lock.
+    if let Some(FileSize(max_filesize)) = parameters.max_filesize {
+        if fs::metadata(path)?.len() > max_filesize {
+            if let Some(ref stats) = parameters.stats {
+                stats.add(Counter::SkippedTooBig, 1);
+            }
+            if parameters.list_skipped_big_files {
+                return Err(NedError::from(
+                    "is bigger than --max-filesize, it was skipped".to_string(),
+                ));
+            }
+            return Ok(false);
+        }
+    }
+    let file = loop {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(parameters.writes_files())
+            .open(path)?;
+        if !parameters.lock || !parameters.writes_files() {
+            break file;
+        }
+        lock_file(&file)?;
+        if Snapshot::new(&file)?.is_current(path) {
+            break file;
+        }
+    };
+    let mut source = Source::File(Box::new(file), path.to_path_buf());
+    // With --json the file name is the path as JSON, so that paths that aren't UTF-8 are kept.
+    let file_name = &Some(if parameters.json {
+        json::path(path)
+    } else {
+        path.to_string_lossy().to_string()
+    });
+    process_source(output, parameters, file_name, &mut source)
+}
+
+/// Process the file or stdin, counting it for --stats if it has matches, and with --json
+/// writing the events of its beginning and end around its other events.
+fn process_source(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &Option<String>,
+    source: &mut Source,
+) -> NedResult<bool> {
+    let json = parameters.json && !parameters.quiet;
+    if json {
+        json::write_begin(output, &json_path(file_name))?;
+    }
+    let found_matches = process_file(output, parameters, file_name, source)?;
+    if let Some(ref stats) = parameters.stats {
+        if found_matches {
+            stats.add(Counter::FilesMatched, 1);
+        }
+    }
+    if json {
+        json::write_end(output, &json_path(file_name), found_matches)?;
+    }
+    Ok(found_matches)
+}
+
+/// The path of the file for --json, which is null for stdin.
+fn json_path(file_name: &Option<String>) -> String {
+    file_name.clone().unwrap_or_else(|| "null".to_string())
+}
+
 fn process_file(
     output: &mut dyn Write,
     parameters: &Parameters,
     file_name: &Option<String>,
     source: &mut Source,
 ) -> NedResult<bool> {
-    let content: String;
-    {
-        let read: &mut dyn Read = match source {
-            Source::Stdin(ref mut read) => read,
-            Source::File(ref mut file) => file,
-            #[cfg(test)]
-            Source::Cursor(ref mut cursor) => cursor,
+    let is_stdin = matches!(source, Source::Stdin(_));
+    // Regular files can be read again, pipes, such as <(...), can't.
+    let rereadable = match source {
+        Source::File(ref file, _) => file.metadata()?.is_file(),
+        _ => false,
+    };
+    // Taken before the file is read, so that a change to it by another process before it is
+    // written can be told.
+    let snapshot = match source {
+        Source::File(ref file, _) if parameters.writes_files() => Some(Snapshot::new(file)?),
+        _ => None,
+    };
+    // Searches read big files through a memory map rather than into a buffer.
+    let mapped = match source {
+        Source::File(ref file, _) if parameters.replace.is_none() => {
+            map_file(parameters.mmap, file)
+        }
+        _ => None,
+    };
+    let read: &mut dyn Read = match source {
+        Source::Stdin(ref mut read) => read,
+        Source::File(ref mut file, _) => file,
+        #[cfg(test)]
+        Source::Cursor(ref mut cursor) => cursor,
+    };
+    let mut counting_read;
+    let read: &mut dyn Read = match parameters.stats {
+        Some(ref stats) => {
+            if let Some(ref mapped) = mapped {
+                stats.add(Counter::BytesRead, mapped.len());
+            }
+            counting_read = stats.counting(read);
+            &mut counting_read
+        }
+        None => read,
+    };
+    // The first block tells whether the content is binary, how it is encoded, and whether it
+    // has CRLFs. Stdin may be a pipe that doesn't end, so it is what it has so far.
+    let mut buffer = Vec::new();
+    if mapped.is_none() {
+        if is_stdin {
+            buffer.resize(DETECTION_LEN, 0);
+            let len = loop {
+                match read.read(&mut buffer) {
+                    Ok(len) => break len,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err.into()),
+                }
+            };
+            buffer.truncate(len);
+        } else {
+            let _ = (&mut *read)
+                .take(DETECTION_LEN as u64)
+                .read_to_end(&mut buffer)?;
+        }
+    }
+    let head = match mapped {
+        Some(ref mapped) => &mapped[..mapped.len().min(DETECTION_LEN)],
+        None => buffer.as_slice(),
+    };
+    let binary = parameters.binary != Binary::Text && is_binary(head);
+    let line_oriented = !parameters.whole_files
+        && !parameters.file_names_only
+        && (parameters.replace.is_none()
+            || is_stdin && parameters.line_buffered && !parameters.diff);
+    if !binary && line_oriented {
+        // Lines of UTF-8, or of bytes, can be processed as they are read, others are decoded
+        // as a whole.
+        let bom_len = if parameters.bytes {
+            Some(0)
+        } else {
+            utf8_bom_len(head, parameters.encoding)
         };
-        let mut buffer = Vec::new();
-        let _ = read.read_to_end(&mut buffer)?;
-        match String::from_utf8(buffer) {
-            Ok(ref parsed) => {
-                content = parsed.to_string();
+        if let Some(bom_len) = bom_len {
+            let re = if has_crlf(head) {
+                parameters.crlf_regex.as_ref()
+            } else {
+                parameters.regex.as_ref()
             }
+            .expect("Bug, already checked parameters.");
+            let byte_offset = FileOffset::new(if parameters.bytes {
+                FileEncoding::Bytes
+            } else if bom_len > 0 {
+                FileEncoding::Utf8Bom
+            } else {
+                FileEncoding::Utf8
+            });
+            let mut reader: Box<dyn BufRead> = match mapped {
+                Some(ref mapped) => Box::new(&mapped[bom_len..]),
+                None => Box::new(BufReader::new(Cursor::new(&head[bom_len..]).chain(read))),
+            };
+            return match parameters.replace {
+                Some(ref replacement) => replace_lines(
+                    output,
+                    parameters,
+                    re,
+                    file_name,
+                    replacement,
+                    &head[..bom_len],
+                    byte_offset,
+                    &mut reader,
+                ),
+                // Bytes are never invalid, and stdin and other pipes may not end, and can't be
+                // read again, so they are shown as they are read.
+                None if parameters.bytes || !rereadable => {
+                    match process_lines(output, parameters, re, file_name, byte_offset, &mut reader)
+                    {
+                        Err(err @ NedError::Utf8(_)) => skip_non_utf8(parameters, err),
+                        result => result,
+                    }
+                }
+                // Nothing is shown of a file that isn't UTF-8, as when it is decoded as a
+                // whole, so files are checked first, and files that aren't mapped are then
+                // read again.
+                None => match mapped {
+                    Some(ref mapped) => match std::str::from_utf8(&mapped[bom_len..]) {
+                        Ok(_) => process_lines(
+                            output,
+                            parameters,
+                            re,
+                            file_name,
+                            byte_offset,
+                            &mut reader,
+                        ),
+                        Err(err) => skip_non_utf8(parameters, err.into()),
+                    },
+                    None => {
+                        match check_utf8_lines(&mut reader) {
+                            Ok(()) => {}
+                            Err(err @ NedError::Utf8(_)) => {
+                                return skip_non_utf8(parameters, err);
+                            }
+                            Err(err) => return Err(err),
+                        }
+                        drop(reader);
+                        match source {
+                            Source::File(ref mut file, _) => {
+                                file.seek(SeekFrom::Start(bom_len as u64))?;
+                                process_lines(
+                                    output,
+                                    parameters,
+                                    re,
+                                    file_name,
+                                    byte_offset,
+                                    &mut BufReader::new(file),
+                                )
+                            }
+                            _ => unreachable!("Bug, only files are read again."),
+                        }
+                    }
+                },
+            };
+        }
+    }
+    let bytes = match mapped {
+        Some(ref mapped) => &mapped[..],
+        None => {
+            let _ = read.read_to_end(&mut buffer)?;
+            buffer.as_slice()
+        }
+    };
+    if binary {
+        return process_binary(output, parameters, file_name, bytes);
+    }
+    // With --bytes the content is matched as it is, otherwise it is decoded to UTF-8.
+    let decoded;
+    let (content, encoding) = if parameters.bytes {
+        (bytes, FileEncoding::Bytes)
+    } else {
+        decoded = match decode(bytes, parameters.encoding) {
+            Ok(decoded) => decoded,
             Err(err) => {
                 if parameters.ignore_non_utf8 {
+                    count_skipped_non_utf8(parameters);
                     return Ok(false);
                 } else {
-                    return Err(NedError::from(err));
+                    return Err(err);
                 }
             }
-        }
-    }
+        };
+        (decoded.0.as_bytes(), decoded.1)
+    };
 
-    let re = parameters
-        .regex
-        .clone()
-        .expect("Bug, already checked parameters.");
+    // ^ and $ match around CRLFs in files that have them.
+    let re = if has_crlf(content) {
+        parameters.crlf_regex.clone()
+    } else {
+        parameters.regex.clone()
+    }
+    .expect("Bug, already checked parameters.");
 
-    if let Some(mut replacement) = parameters.replace.clone() {
-        if parameters.colors {
-            replacement = Red.bold().paint(replacement.as_str()).to_string();
-        }
-        if parameters.case_replacements {
-            replacement = replace_case_escape_sequences_with_special_strings(&replacement);
-        }
-        let (content, found_matches) = replace(parameters, &re, &content, &replacement);
-        let content = if parameters.case_replacements {
-            replace_case_with_special_strings(&content)
-        } else {
-            content
+    if let Some(replacement) = parameters.replace.clone() {
+        let replacement = prepare_replacement(parameters, content, &replacement);
+        let (new_content, spans) = match parameters.interactive {
+            Some(ref interactive) => replace_interactively(
+                parameters,
+                interactive,
+                &re,
+                file_name,
+                content,
+                replacement.as_bytes(),
+                &mut stderr(),
+                &mut stdin().lock(),
+            )?,
+            None => replace(parameters, &re, content, replacement.as_bytes()),
         };
-        if parameters.stdout {
-            if !parameters.quiet {
-                write_file_name_and_line_number(output, parameters, file_name, None)?;
-                output.write_all(&content.into_bytes())?;
+        let found_matches = !spans.is_empty();
+        if parameters.json && !parameters.quiet {
+            write_replace_events(
+                output,
+                parameters,
+                &re,
+                file_name,
+                1,
+                FileOffset::new(encoding),
+                content,
+                &spans,
+                replacement.as_bytes(),
+            )?;
+        }
+        let new_content = finish_replacement(parameters, new_content);
+        if parameters.diff {
+            if !parameters.quiet && !parameters.json && found_matches {
+                let file_name = file_name.as_deref().unwrap_or("-");
+                write_unified_diff(
+                    output,
+                    parameters,
+                    file_name,
+                    &escape_invalid_utf8(content),
+                    &escape_invalid_utf8(&new_content),
+                )?;
+            }
+        } else if parameters.stdout {
+            if !parameters.quiet && !parameters.json {
+                write_file_name_and_line_number(output, parameters, file_name, None, None)?;
+                // Replacing from stdin to stdout is a filter, so it keeps the encoding.
+                if parameters.stdin {
+                    output.write_all(&encoding.encode(&new_content)?)?;
+                } else {
+                    output.write_all(&new_content)?;
+                }
             }
         } else {
             // It's not a single match in test.
             #[allow(clippy::single_match)]
             match source {
                 // A better way???
-                Source::File(ref mut file) => {
-                    if found_matches {
-                        file.seek(SeekFrom::Start(0))?;
-                        let bytes = &content.into_bytes();
-                        file.write_all(bytes)?;
-                        file.set_len(bytes.len() as u64)?;
+                Source::File(ref mut file, ref path) if found_matches => {
+                    let new_content = encoding.encode(&new_content)?;
+                    if let Some(ref snapshot) = snapshot {
+                        snapshot.check(path)?;
+                    }
+                    if parameters.backup.is_some() {
+                        backup_file(parameters, path)?;
+                    }
+                    if let Some(ref journal) = parameters.journal {
+                        journal.record(path, bytes, &new_content)?;
+                    }
+                    for warning in
+                        rewrite_file(path, file, &new_content, parameters.preserve_mtime)?
+                    {
+                        stderr_write_file_err(path, &warning);
+                    }
+                    if let Some(ref stats) = parameters.stats {
+                        stats.add(Counter::FilesModified, 1);
+                        stats.add(Counter::BytesWritten, new_content.len());
                     }
                 }
                 #[cfg(test)]
                 Source::Cursor(ref mut cursor) => {
                     cursor.seek(SeekFrom::Start(0))?;
-                    cursor.write_all(&content.into_bytes())?;
+                    cursor.write_all(&encoding.encode(&new_content)?)?;
+                    let len = cursor.position() as usize;
+                    cursor.get_mut().truncate(len);
                 }
                 _ => {}
             }
         }
         Ok(found_matches)
     } else if parameters.file_names_only {
-        let found_matches = re.is_match(&content);
+        let found_matches = re.is_match(content);
+        if let Some(ref stats) = parameters.stats {
+            let matches = Matches::find(parameters, &re, content);
+            stats.add(Counter::Matches, matches.selected(parameters).count());
+        }
         if found_matches ^ parameters.no_match {
-            write_file_name_and_line_number(output, parameters, file_name, None)?;
+            write_file_name_and_line_number(output, parameters, file_name, None, None)?;
         }
         Ok(found_matches)
     } else if !parameters.whole_files {
-        let mut found_matches = false;
-        let context_map = make_context_map(parameters, &re, &content);
-        for (index, line) in content.lines().enumerate() {
-            let line_number = index + 1;
+        let mut lines = content;
+        process_lines(
+            output,
+            parameters,
+            &re,
+            file_name,
+            FileOffset::new(encoding),
+            &mut lines,
+        )
+    } else {
+        let found_matches = if only_is_match(parameters) {
+            re.is_match(content)
+        } else {
+            let matches = Matches::find(parameters, &re, content);
+            if parameters.counts() {
+                let (found_matches, count) = counted(parameters, &matches);
+                write_count(output, parameters, file_name, count)?;
+                found_matches
+            } else {
+                process_text(
+                    output,
+                    parameters,
+                    &re,
+                    file_name,
+                    None,
+                    FileOffset::new(encoding),
+                    content,
+                    &matches,
+                    false,
+                )?
+            }
+        };
+        Ok(found_matches)
+    }
+}
+
+/// Count a file that was skipped because it isn't UTF-8, for --stats.
+fn count_skipped_non_utf8(parameters: &Parameters) {
+    if let Some(ref stats) = parameters.stats {
+        stats.add(Counter::SkippedNonUtf8, 1);
+    }
+}
+
+/// Binary files are reported when they match, the way grep does, rather than having their
+/// matching lines shown, and aren't replaced in. With --binary=skip they aren't looked at.
+fn process_binary(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &Option<String>,
+    content: &[u8],
+) -> NedResult<bool> {
+    if parameters.binary == Binary::Skip {
+        if let Some(ref stats) = parameters.stats {
+            stats.add(Counter::SkippedBinary, 1);
+        }
+        if parameters.list_skipped_binaries {
+            return Err(NedError::from(
+                "is a binary file, it was skipped".to_string(),
+            ));
+        }
+        return Ok(false);
+    }
+    let re = parameters
+        .regex
+        .as_ref()
+        .expect("Bug, already checked parameters.");
+    let found_matches = re.is_match(content);
+    let name = file_name.as_deref().unwrap_or("(standard input)");
+    if parameters.replace.is_some() {
+        // Replacing from stdin to stdout is a filter, so the content passes through as it is.
+        if parameters.stdin && !parameters.diff && !parameters.json && !parameters.quiet {
+            output.write_all(content)?;
+        }
+        if found_matches {
+            return Err(NedError::from(
+                "is a binary file, it was not replaced".to_string(),
+            ));
+        }
+    } else if !parameters.quiet && found_matches ^ parameters.no_match {
+        if parameters.json {
+            json::write_binary(output, &json_path(file_name))?;
+        } else if parameters.file_names_only {
+            write_file_name_and_line_number(output, parameters, file_name, None, None)?;
+        } else {
+            output.write_all(format!("Binary file {} matches\n", name).as_bytes())?;
+        }
+    }
+    Ok(found_matches)
+}
+
+/// Check that the lines are UTF-8, before they are searched.
+fn check_utf8_lines(reader: &mut dyn BufRead) -> NedResult<()> {
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        std::str::from_utf8(&line)?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// A file that isn't UTF-8 is quietly skipped with -u/--ignore-non-utf8, and is an error
+/// otherwise.
+fn skip_non_utf8(parameters: &Parameters, err: NedError) -> NedResult<bool> {
+    if parameters.ignore_non_utf8 {
+        count_skipped_non_utf8(parameters);
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+/// Search the lines as they are read, holding only the ones that may be --before context of
+/// a match yet to come, so that files of any size can be searched in bounded memory.
+fn process_lines(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    re: &Regex,
+    file_name: &Option<String>,
+    byte_offset: FileOffset,
+    reader: &mut dyn BufRead,
+) -> NedResult<bool> {
+    let mut found_matches = false;
+    let mut before = VecDeque::<(usize, FileOffset, Vec<u8>, Matches)>::with_capacity(
+        parameters.context_before + 1,
+    );
+    let mut after = 0;
+    let mut count = 0;
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut next_byte_offset = byte_offset;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        line_number += 1;
+        let byte_offset = next_byte_offset;
+        next_byte_offset = byte_offset.after(&line);
+        if !parameters.bytes {
+            std::str::from_utf8(&line)?;
+        }
+        let text = strip_line_ending(&line);
+        if only_is_match(parameters) {
+            if re.is_match(text) {
+                return Ok(true);
+            }
+            line.clear();
+            continue;
+        }
+        let matches = Matches::find(parameters, re, text);
+        if parameters.counts() {
+            let (found, line_count) = counted(parameters, &matches);
+            found_matches |= found;
+            count += line_count;
+        } else if matches.any_selected(parameters) {
+            for (line_number, byte_offset, text, matches) in before.drain(..) {
+                found_matches |= process_text(
+                    output,
+                    parameters,
+                    re,
+                    file_name,
+                    Some(line_number),
+                    byte_offset,
+                    &text,
+                    &matches,
+                    true,
+                )?;
+            }
             found_matches |= process_text(
                 output,
                 parameters,
-                &re,
+                re,
                 file_name,
                 Some(line_number),
-                line,
-                Some(&context_map),
+                byte_offset,
+                text,
+                &matches,
+                true,
             )?;
-            if parameters.quiet && found_matches {
-                break;
+            after = parameters.context_after;
+        } else if after > 0 {
+            found_matches |= process_text(
+                output,
+                parameters,
+                re,
+                file_name,
+                Some(line_number),
+                byte_offset,
+                text,
+                &matches,
+                true,
+            )?;
+            after -= 1;
+        } else if parameters.context_before > 0 {
+            before.push_back((line_number, byte_offset, text.to_vec(), matches));
+            if before.len() > parameters.context_before {
+                let (line_number, byte_offset, text, matches) =
+                    before.pop_front().expect("Bug, it was just pushed.");
+                found_matches |= process_text(
+                    output,
+                    parameters,
+                    re,
+                    file_name,
+                    Some(line_number),
+                    byte_offset,
+                    &text,
+                    &matches,
+                    false,
+                )?;
             }
+        } else {
+            found_matches |= process_text(
+                output,
+                parameters,
+                re,
+                file_name,
+                Some(line_number),
+                byte_offset,
+                text,
+                &matches,
+                false,
+            )?;
         }
-        Ok(found_matches)
-    } else {
-        let found_matches = process_text(output, parameters, &re, file_name, None, &content, None)?;
-        Ok(found_matches)
+        if parameters.quiet && found_matches {
+            return Ok(true);
+        }
+        if parameters.line_buffered {
+            output.flush()?;
+        }
+        line.clear();
+    }
+    for (line_number, byte_offset, text, matches) in before {
+        found_matches |= process_text(
+            output,
+            parameters,
+            re,
+            file_name,
+            Some(line_number),
+            byte_offset,
+            &text,
+            &matches,
+            false,
+        )?;
+    }
+    if parameters.counts() {
+        write_count(output, parameters, file_name, count)?;
     }
+    Ok(found_matches)
 }
 
-/// Returns a vector whose capacity equals the number of lines in the file, and whose
-/// value is a boolean that indicates whether or not that line should be shown given
-/// the -C --context, -B --before, and -A --after options specified in the parameters.
-fn make_context_map(parameters: &Parameters, re: &Regex, content: &str) -> Vec<bool> {
-    let lines = content.lines().map(str::to_string).collect::<Vec<String>>();
-    let mut match_map = Vec::<bool>::with_capacity(lines.len());
-    for line in lines {
-        match_map.push(is_match_with_number_skip_backwards(parameters, re, &line));
+/// Whether the matches of a line, or of a whole file, are found matches, as they are by
+/// process_text(), and what they add to --count, the lines with matches selected by --number,
+/// --skip, and --backwards, or with -v/--no-match the lines without any, or to
+/// --count-matches, the matches selected.
+fn counted(parameters: &Parameters, matches: &Matches) -> (bool, usize) {
+    if let Some(ref stats) = parameters.stats {
+        stats.add(Counter::Matches, matches.selected(parameters).count());
     }
-    let mut context_map = match_map.clone();
-    for line in 0..context_map.len() {
-        if match_map[line] {
-            // We can't use std::cmp::max() for this test because the indices are unsigned.
-            let start = if line >= parameters.context_before {
-                line - parameters.context_before
-            } else {
-                0usize
-            };
-            let end = std::cmp::min(match_map.len(), line + parameters.context_after + 1);
-            for item in context_map.iter_mut().take(end).skip(start) {
-                *item = true;
-            }
-        }
+    if parameters.no_match {
+        (!matches.is_empty(), usize::from(matches.is_empty()))
+    } else if parameters.count_matches {
+        let count = matches.selected(parameters).count();
+        (count > 0, count)
+    } else {
+        let found_matches = matches.any_selected(parameters);
+        (found_matches, usize::from(found_matches))
     }
-    context_map
 }
 
-fn is_match_with_number_skip_backwards(parameters: &Parameters, re: &Regex, text: &str) -> bool {
-    let start_end_byte_indices = re.find_iter(text);
-    let count = start_end_byte_indices.count();
-    for index in 0..count {
-        if parameters.include_match(index, count) {
-            return true;
-        }
+/// Write the file's --count or --count-matches. Files without any aren't shown, but stdin's is
+/// always shown.
+fn write_count(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &Option<String>,
+    count: usize,
+) -> NedResult<()> {
+    if count > 0 || file_name.is_none() {
+        write_file_name_and_line_number(output, parameters, file_name, Some(count), None)?;
     }
-    false
+    Ok(())
 }
 
+/// Whether all that matters is whether there are matches, as it is when quiet, without
+/// anything that selects which of them count. That is shortcut by the more performant
+/// is_match().
+fn only_is_match(parameters: &Parameters) -> bool {
+    parameters.quiet && !parameters.limit_matches() && parameters.group.is_none()
+}
+
+#[allow(clippy::too_many_arguments)]
 fn process_text(
     output: &mut dyn Write,
     parameters: &Parameters,
     re: &Regex,
     file_name: &Option<String>,
     line_number: Option<usize>,
-    text: &str,
-    context_map: Option<&Vec<bool>>,
+    byte_offset: FileOffset,
+    text: &[u8],
+    matches: &Matches,
+    in_context: bool,
 ) -> NedResult<bool> {
-    if parameters.quiet && !parameters.limit_matches() && parameters.group.is_none() {
-        // Quiet match only is shortcut by the more performant is_match() .
-        return Ok(re.is_match(text));
+    if let Some(ref stats) = parameters.stats {
+        stats.add(Counter::Matches, matches.selected(parameters).count());
     }
-    if let Some(ref group) = parameters.group {
-        // TODO 2: make it respect -n, -k, -b TO TEST
-        return write_groups(output, parameters, re, file_name, line_number, text, group);
+    if parameters.format.is_some() {
+        // Lines without matches are shown with -v/--no-match, and context isn't shown.
+        let found_matches = if parameters.no_match {
+            !matches.is_empty()
+        } else {
+            matches.any_selected(parameters)
+        };
+        if !parameters.quiet {
+            let mut locator = Locator::new(text, line_number, byte_offset);
+            if parameters.no_match && !found_matches {
+                write_formatted(output, parameters, re, file_name, text, &mut locator, None)?;
+            } else if !parameters.no_match {
+                for span in matches.selected(parameters) {
+                    write_formatted(
+                        output,
+                        parameters,
+                        re,
+                        file_name,
+                        text,
+                        &mut locator,
+                        Some(span),
+                    )?;
+                }
+            }
+        }
+        return Ok(found_matches);
+    } else if let Some(ref group) = parameters.group {
+        return write_groups(
+            output,
+            parameters,
+            re,
+            file_name,
+            line_number,
+            byte_offset,
+            text,
+            matches,
+            group,
+        );
     } else if parameters.no_match {
-        let found_matches = re.is_match(text);
-        if !found_matches {
-            write_line(output, parameters, file_name, line_number, text)?;
+        let found_matches = !matches.is_empty();
+        if !found_matches && parameters.json {
+            if !parameters.quiet {
+                let path = json_path(file_name);
+                json::write_match(
+                    output,
+                    re,
+                    &path,
+                    line_number,
+                    byte_offset.start(),
+                    text,
+                    iter::empty(),
+                )?;
+            }
+        } else if !found_matches && parameters.vimgrep {
+            write_vimgrep_line(
+                output,
+                parameters,
+                file_name,
+                text,
+                &mut Locator::new(text, line_number, byte_offset),
+                0,
+            )?;
+        } else if !found_matches {
+            let shown = shown_len(parameters, text);
+            write_line(
+                output,
+                parameters,
+                file_name,
+                line_number,
+                position(parameters, text, byte_offset, 0),
+                &text[..shown],
+                text.len() - shown,
+            )?;
         }
         return Ok(found_matches);
-    } else if re.is_match(text) {
-        if parameters.matches_only {
-            if write_matches(output, parameters, re, file_name, line_number, text)? {
-                return Ok(true);
+    } else if matches.any_selected(parameters) {
+        if parameters.json {
+            if !parameters.quiet {
+                let path = json_path(file_name);
+                let spans = matches.selected(parameters);
+                json::write_match(
+                    output,
+                    re,
+                    &path,
+                    line_number,
+                    byte_offset.start(),
+                    text,
+                    spans,
+                )?;
             }
+        } else if parameters.matches_only || parameters.vimgrep {
+            write_matches(
+                output,
+                parameters,
+                re,
+                file_name,
+                line_number,
+                byte_offset,
+                text,
+                matches,
+            )?;
         } else {
-            // TODO 4: make it respect -n, -k, -b TO TEST
-            // Need to get is found_matches out of this...
-            let (text, found_matches) =
-                color_matches_with_number_skip_backwards(parameters, re, text);
-            if found_matches {
-                write_line(output, parameters, file_name, line_number, &text)?;
-                return Ok(true);
-            }
+            let shown = shown_len(parameters, text);
+            let highlighted =
+                highlight_matches(parameters, re, text, shown, matches.selected(parameters));
+            let first = matches
+                .selected(parameters)
+                .next()
+                .map_or(0, |span| span.start);
+            write_line(
+                output,
+                parameters,
+                file_name,
+                line_number,
+                position(parameters, text, byte_offset, first),
+                &highlighted,
+                text.len() - shown,
+            )?;
         }
+        return Ok(true);
     }
 
-    if let Some(line_number) = line_number {
-        if let Some(context_map) = context_map {
-            if !context_map.is_empty() && context_map[line_number - 1] {
-                write_line(output, parameters, file_name, Some(line_number), text)?;
-            }
+    if in_context && parameters.json {
+        if !parameters.quiet {
+            let path = json_path(file_name);
+            json::write_context(output, &path, line_number, byte_offset.start(), text)?;
         }
+    } else if in_context && !parameters.vimgrep {
+        let shown = shown_len(parameters, text);
+        write_line(
+            output,
+            parameters,
+            file_name,
+            line_number,
+            position(parameters, text, byte_offset, 0),
+            &text[..shown],
+            text.len() - shown,
+        )?;
     }
     Ok(false)
 }
 
-/// Do a replace_all() or a find_iter() taking into account which of --number, --skip, and
-/// --backwards have been specified.
-fn replace(parameters: &Parameters, re: &Regex, text: &str, replace: &str) -> (String, bool) {
+/// Replace in the lines of stdin as they are read, writing each one out as it is done, so
+/// that ned can filter a pipe that doesn't end.
+#[allow(clippy::too_many_arguments)]
+fn replace_lines(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    re: &Regex,
+    file_name: &Option<String>,
+    replacement: &str,
+    bom: &[u8],
+    byte_offset: FileOffset,
+    reader: &mut dyn BufRead,
+) -> NedResult<bool> {
     let mut found_matches = false;
-    let mut new_text;
-    if !parameters.limit_matches() {
-        found_matches = re.is_match(text);
-        new_text = re.replace_all(text, replace).into_owned()
-    } else {
-        new_text = text.to_string();
-        let start_end_byte_indices = re.find_iter(text).collect::<Vec<Match>>();
-        let count = start_end_byte_indices.len();
-        // Walk it backwards so that replacements don't invalidate indices.
-        for (rev_index, &_match) in start_end_byte_indices.iter().rev().enumerate() {
-            let index = count - rev_index - 1;
-            if parameters.include_match(index, count) {
-                found_matches = true;
-                let this_replace = re.replace(_match.as_str(), replace).into_owned();
-                new_text = format!(
-                    "{}{}{}",
-                    // find_iter guarantees that start and end
-                    // are at a Unicode code point boundary.
-                    unsafe { &new_text.get_unchecked(0.._match.start()) },
-                    this_replace,
-                    unsafe { &new_text.get_unchecked(_match.end()..new_text.len()) }
-                );
+    if !parameters.quiet && !parameters.json {
+        output.write_all(bom)?;
+    }
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut next_byte_offset = byte_offset;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        line_number += 1;
+        let byte_offset = next_byte_offset;
+        next_byte_offset = byte_offset.after(&line);
+        if !parameters.bytes {
+            if let Err(err) = std::str::from_utf8(&line) {
+                if parameters.ignore_non_utf8 {
+                    count_skipped_non_utf8(parameters);
+                    return Ok(false);
+                } else {
+                    return Err(err.into());
+                }
             }
         }
+        let text = strip_line_ending(&line);
+        let replacement = prepare_replacement(parameters, &line, replacement);
+        let (mut new_line, spans) = replace(parameters, re, text, replacement.as_bytes());
+        found_matches |= !spans.is_empty();
+        if parameters.quiet && found_matches {
+            return Ok(true);
+        }
+        if parameters.json {
+            write_replace_events(
+                output,
+                parameters,
+                re,
+                file_name,
+                line_number,
+                byte_offset,
+                text,
+                &spans,
+                replacement.as_bytes(),
+            )?;
+        } else {
+            new_line.extend_from_slice(&line[text.len()..]);
+            output.write_all(&finish_replacement(parameters, new_line))?;
+        }
+        if parameters.line_buffered {
+            output.flush()?;
+        }
+        line.clear();
+    }
+    Ok(found_matches)
+}
+
+/// The replacement in the line endings of the text it is replacing in, colored, and with its
+/// case escapes marked.
+fn prepare_replacement(parameters: &Parameters, text: &[u8], replacement: &str) -> String {
+    let mut replacement = parameters.eol.adapt_replacement(text, replacement);
+    if parameters.colors && !parameters.diff {
+        replacement = Highlight::Replacement
+            .style()
+            .paint(replacement.as_str())
+            .to_string();
+    }
+    if parameters.case_replacements {
+        replacement = replace_case_escape_sequences_with_special_strings(&replacement);
+    }
+    replacement
+}
+
+/// The replaced text with the case escapes of the replacements applied, and its line endings
+/// made what --eol says.
+fn finish_replacement(parameters: &Parameters, new_text: Vec<u8>) -> Vec<u8> {
+    let new_text = if parameters.case_replacements {
+        replace_case_with_special_strings(&new_text)
+    } else {
+        new_text
     };
-    (new_text, found_matches)
+    parameters.eol.normalize(new_text)
+}
+
+/// Replace the matches selected by --number, --skip, and --backwards, returning the new text
+/// and the spans of the text that were replaced.
+fn replace(
+    parameters: &Parameters,
+    re: &Regex,
+    text: &[u8],
+    replace: &[u8],
+) -> (Vec<u8>, Vec<Range<usize>>) {
+    let matches = Matches::find(parameters, re, text);
+    let spans = matches.selected(parameters).collect::<Vec<&Range<usize>>>();
+    if let Some(ref stats) = parameters.stats {
+        stats.add(Counter::Matches, spans.len());
+        stats.add(Counter::Replacements, spans.len());
+    }
+    (
+        splice(re, text, replace, &spans),
+        spans.into_iter().cloned().collect(),
+    )
+}
+
+/// Do a replace asking which of the matches selected by --number, --skip, and --backwards to
+/// replace, returning the new text and the spans of the text that were replaced.
+#[allow(clippy::too_many_arguments)]
+fn replace_interactively(
+    parameters: &Parameters,
+    interactive: &Interactive,
+    re: &Regex,
+    file_name: &Option<String>,
+    text: &[u8],
+    replace: &[u8],
+    prompt: &mut dyn Write,
+    answers: &mut dyn BufRead,
+) -> NedResult<(Vec<u8>, Vec<Range<usize>>)> {
+    let replacements = Matches::find(parameters, re, text)
+        .selected(parameters)
+        .map(|span| {
+            (
+                span.clone(),
+                expand_replacement(parameters, re, text, span, replace),
+            )
+        })
+        .collect::<Vec<(Range<usize>, Vec<u8>)>>();
+    let accepted =
+        interactive.confirm(parameters, file_name, text, &replacements, prompt, answers)?;
+    if let Some(ref stats) = parameters.stats {
+        stats.add(Counter::Matches, replacements.len());
+        stats.add(Counter::Replacements, accepted.len());
+    }
+    Ok((
+        splice(re, text, replace, &accepted),
+        accepted.into_iter().cloned().collect(),
+    ))
+}
+
+/// The replacement of the match at the span on its own, with its groups expanded and its case
+/// escapes done.
+fn expand_replacement(
+    parameters: &Parameters,
+    re: &Regex,
+    text: &[u8],
+    span: &Range<usize>,
+    replace: &[u8],
+) -> Vec<u8> {
+    let mut replacement = Vec::new();
+    captures_at(re, text, span).expand(replace, &mut replacement);
+    if parameters.case_replacements {
+        replacement = replace_case_with_special_strings(&replacement);
+    }
+    replacement
+}
+
+/// Write a --json replace event for each of the spans of text that were replaced, text being
+/// the part of the file that starts at the byte offset, on the line.
+#[allow(clippy::too_many_arguments)]
+fn write_replace_events(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    re: &Regex,
+    file_name: &Option<String>,
+    mut line_number: usize,
+    byte_offset: FileOffset,
+    text: &[u8],
+    spans: &[Range<usize>],
+    replace: &[u8],
+) -> NedResult<()> {
+    let path = json_path(file_name);
+    let mut last_start = 0;
+    let mut byte_offset = byte_offset;
+    for span in spans {
+        line_number += text[last_start..span.start]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count();
+        byte_offset = byte_offset.after(&text[last_start..span.start]);
+        last_start = span.start;
+        let matched = &text[span.clone()];
+        json::write_replace(
+            output,
+            &path,
+            line_number,
+            byte_offset.start()..byte_offset.at(matched, matched.len()),
+            matched,
+            &expand_replacement(parameters, re, text, span, replace),
+        )?;
+    }
+    Ok(())
+}
+
+/// The captures of the match of re at the start of the span, which is one of the matches that
+/// Matches found.
+fn captures_at<'t>(re: &Regex, text: &'t [u8], span: &Range<usize>) -> Captures<'t> {
+    re.captures_at(text, span.start)
+        .expect("Bug, the span is where a match is.")
+}
+
+/// Replace the matches at the spans, which are in the order they are in text, with replace
+/// expanded with each match's groups.
+fn splice(re: &Regex, text: &[u8], replace: &[u8], spans: &[&Range<usize>]) -> Vec<u8> {
+    // Only replacements with a $ have groups to expand, so only they need the captures.
+    let expand = replace.contains(&b'$');
+    let mut new_text = Vec::with_capacity(text.len());
+    let mut last_end = 0;
+    for span in spans {
+        new_text.extend_from_slice(&text[last_end..span.start]);
+        if expand {
+            captures_at(re, text, span).expand(replace, &mut new_text);
+        } else {
+            new_text.extend_from_slice(replace);
+        }
+        last_end = span.end;
+    }
+    new_text.extend_from_slice(&text[last_end..]);
+    new_text
+}
+
+/// The line without its LF or CRLF ending, as str::lines() gives it.
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
 }
 
 enum CaseEscape {
@@ -387,49 +1344,59 @@ enum CaseEscape {
 fn replace_case_escape_sequences_with_special_strings(str: &str) -> String {
     // Convert \U etc. into --nedUned--- etc. so that they should
     // never clash with something in a real file, you'd think!
-    Regex::new(r"\\(U|L|I|F|E)")
-        .unwrap()
+    static CASE_ESCAPE_SEQUENCE: OnceLock<regex::Regex> = OnceLock::new();
+    CASE_ESCAPE_SEQUENCE
+        .get_or_init(|| regex::Regex::new(r"\\(U|L|I|F|E)").unwrap())
         .replace_all(str, "--ned${1}ned--")
         .into_owned()
 }
 
-fn replace_case_with_special_strings(str: &str) -> String {
-    let mut escapes = HashMap::new();
-    escapes.insert("U", CaseEscape::Upper);
-    escapes.insert("L", CaseEscape::Lower);
-    escapes.insert("I", CaseEscape::Initial);
-    escapes.insert("F", CaseEscape::First);
-    escapes.insert("E", CaseEscape::End);
+fn replace_case_with_special_strings(text: &[u8]) -> Vec<u8> {
+    let mut escapes = HashMap::<&[u8], CaseEscape>::new();
+    escapes.insert(b"U", CaseEscape::Upper);
+    escapes.insert(b"L", CaseEscape::Lower);
+    escapes.insert(b"I", CaseEscape::Initial);
+    escapes.insert(b"F", CaseEscape::First);
+    escapes.insert(b"E", CaseEscape::End);
     let escapes = escapes;
 
-    let mut result = String::new();
+    let mut result = Vec::new();
     let mut last_end = 0;
     let mut last_case_escape = &CaseEscape::End;
 
-    for _match in Regex::new(r"--ned(U|L|I|F|E)ned--").unwrap().find_iter(str) {
+    static SPECIAL_STRING: OnceLock<Regex> = OnceLock::new();
+    for _match in SPECIAL_STRING
+        .get_or_init(|| Regex::new(r"--ned(U|L|I|F|E)ned--").unwrap())
+        .find_iter(text)
+    {
         let (start, end) = (_match.start(), _match.end());
-        let piece = &str[last_end..start];
-        let case_escape = &str[start + 5..end - 5];
+        let piece = &text[last_end..start];
+        let case_escape = &text[start + 5..end - 5];
         // It must be there because the definition of escapes matches the regex, so unwrap.
         let case_escape = &escapes[case_escape];
         // Apply the last escape to the current piece,
         // append it to the result, clear the current
         // piece, and remember the escape we just found.
         let piece = apply_case_escape(last_case_escape, piece);
-        result.push_str(&piece);
+        result.extend_from_slice(&piece);
         last_end = end;
         last_case_escape = case_escape;
     }
     // Apply the last escape to the remaining piece
     // when we've hit the end of the string.
-    let piece = &str[last_end..];
+    let piece = &text[last_end..];
     let piece = apply_case_escape(last_case_escape, piece);
-    result.push_str(&piece);
+    result.extend_from_slice(&piece);
     result
 }
 
-fn apply_case_escape(case_escape: &CaseEscape, piece: &str) -> String {
-    match case_escape {
+/// Change the case of the piece, unless it isn't UTF-8, which can only be when matching
+/// --bytes.
+fn apply_case_escape<'p>(case_escape: &CaseEscape, piece: &'p [u8]) -> Cow<'p, [u8]> {
+    let Ok(piece) = std::str::from_utf8(piece) else {
+        return Cow::Borrowed(piece);
+    };
+    let piece = match case_escape {
         CaseEscape::Upper => piece.to_uppercase(),
         CaseEscape::Lower => piece.to_lowercase(),
         CaseEscape::Initial => piece
@@ -439,7 +1406,8 @@ fn apply_case_escape(case_escape: &CaseEscape, piece: &str) -> String {
             .join(" "),
         CaseEscape::First => title_case(piece),
         CaseEscape::End => piece.to_string(),
-    }
+    };
+    Cow::Owned(piece.into_bytes())
 }
 
 fn title_case(str: &str) -> String {
@@ -457,58 +1425,104 @@ fn title_case(str: &str) -> String {
     result
 }
 
+/// How much of the line is shown, all of it unless it is longer than --max-line-length, when
+/// it is cut there, or before the character that would be split there.
+fn shown_len(parameters: &Parameters, text: &[u8]) -> usize {
+    match parameters.max_line_length {
+        Some(max_line_length) if !parameters.whole_files && text.len() > max_line_length => {
+            let mut len = max_line_length;
+            while !parameters.bytes && len > 0 && text[len] & 0xc0 == 0x80 {
+                len -= 1;
+            }
+            len
+        }
+        _ => text.len(),
+    }
+}
+
+/// Whether --column or --byte-offset show where matches are.
+fn shows_position(parameters: &Parameters) -> bool {
+    parameters.column.is_some() || parameters.byte_offset
+}
+
+/// The position of the byte at index in text, which starts at byte_offset in its file, if
+/// --column or --byte-offset show it.
+fn position(
+    parameters: &Parameters,
+    text: &[u8],
+    byte_offset: FileOffset,
+    index: usize,
+) -> Option<Position> {
+    shows_position(parameters).then(|| Position::new(text, byte_offset, index, parameters.column))
+}
+
+/// Write the line, followed by how many more bytes it has when it has been cut by
+/// --max-line-length.
 fn write_line(
     output: &mut dyn Write,
     parameters: &Parameters,
     file_name: &Option<String>,
     line_number: Option<usize>,
-    text: &str,
+    position: Option<Position>,
+    text: &[u8],
+    more: usize,
 ) -> NedResult<()> {
     if !parameters.quiet {
-        write_file_name_and_line_number(output, parameters, file_name, line_number)?;
+        write_file_name_and_line_number(output, parameters, file_name, line_number, position)?;
         if !parameters.line_numbers_only && !parameters.quiet {
-            output.write_all(&text.to_string().into_bytes())?;
+            output.write_all(escape_invalid_utf8(text).as_bytes())?;
+            if more > 0 {
+                output.write_all(format!(" [... {} more bytes]", more).as_bytes())?;
+            }
             write_newline_if_replaced_text_ends_with_newline(output, text)?;
         }
     }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn write_groups(
     output: &mut dyn Write,
     parameters: &Parameters,
     re: &Regex,
     file_name: &Option<String>,
     line_number: Option<usize>,
-    text: &str,
+    byte_offset: FileOffset,
+    text: &[u8],
+    matches: &Matches,
     group: &str,
 ) -> NedResult<bool> {
     let mut wrote_file_name = false;
     let mut found_matches = false;
-    let captures = re.captures_iter(text).collect::<Vec<Captures>>();
-    for (index, capture) in captures.iter().enumerate() {
-        if parameters.include_match(index, captures.len()) {
-            let _match = match group.trim().parse::<usize>() {
-                Ok(index) => capture.get(index),
-                Err(_) => capture.name(group),
-            };
-            if let Some(_match) = _match {
-                found_matches = true;
-                if !parameters.quiet {
-                    let text = color_matches_all(parameters, re, _match.as_str());
-                    if !wrote_file_name {
-                        write_file_name_and_line_number(
-                            output,
-                            parameters,
-                            file_name,
-                            line_number,
-                        )?;
-                        wrote_file_name = true;
-                    }
-                    output.write_all(&text.to_string().into_bytes())?;
-                } else {
-                    break;
+    for span in matches.selected(parameters) {
+        let capture = captures_at(re, text, span);
+        let _match = match group.trim().parse::<usize>() {
+            Ok(index) => capture.get(index),
+            Err(_) => capture.name(group),
+        };
+        if let Some(_match) = _match {
+            found_matches = true;
+            if !parameters.quiet {
+                if !wrote_file_name {
+                    let position = position(parameters, text, byte_offset, _match.start());
+                    write_file_name_and_line_number(
+                        output,
+                        parameters,
+                        file_name,
+                        line_number,
+                        position,
+                    )?;
+                    wrote_file_name = true;
                 }
+                // The group is what was asked for, so all of it is highlighted as the match.
+                let text = if parameters.colors {
+                    paint(text, _match.range(), &[(_match.range(), Highlight::Match)])
+                } else {
+                    Cow::Borrowed(_match.as_bytes())
+                };
+                output.write_all(escape_invalid_utf8(&text).as_bytes())?;
+            } else {
+                break;
             }
         }
     }
@@ -520,37 +1534,124 @@ fn write_groups(
 
 /// Write matches taking into account which of --number, --skip, and --backwards have been
 /// specified.
+#[allow(clippy::too_many_arguments)]
 fn write_matches(
     output: &mut dyn Write,
     parameters: &Parameters,
     re: &Regex,
     file_name: &Option<String>,
     line_number: Option<usize>,
-    text: &str,
-) -> NedResult<bool> {
-    let mut found_matches = false;
+    byte_offset: FileOffset,
+    text: &[u8],
+    matches: &Matches,
+) -> NedResult<()> {
+    // With --column or --byte-offset each match is on its own line with its own position.
+    let each_on_its_own_line = shows_position(parameters);
     let mut file_name_written = false;
-    let start_end_byte_indices = re.find_iter(text).collect::<Vec<Match>>();
-    let count = start_end_byte_indices.len();
-    for (index, &_match) in start_end_byte_indices.iter().enumerate() {
-        if parameters.include_match(index, count) {
-            found_matches = true;
-            if !file_name_written {
-                write_file_name_and_line_number(output, parameters, file_name, line_number)?;
-                file_name_written = true;
-            }
-            let text = color(parameters, &text[_match.start().._match.end()]);
-            if !parameters.quiet {
-                output.write_all(&text.to_string().into_bytes())?;
-            } else {
-                return Ok(found_matches);
+    let mut locator = Locator::new(text, line_number, byte_offset);
+    for span in matches.selected(parameters) {
+        if parameters.vimgrep {
+            write_vimgrep_line(
+                output,
+                parameters,
+                file_name,
+                text,
+                &mut locator,
+                span.start,
+            )?;
+            if parameters.quiet {
+                return Ok(());
             }
+            continue;
+        }
+        if file_name_written && each_on_its_own_line && !parameters.quiet {
+            output.write_all(b"\n")?;
         }
+        if !file_name_written || each_on_its_own_line {
+            let position =
+                shows_position(parameters).then(|| locator.position(span.start, parameters.column));
+            write_file_name_and_line_number(output, parameters, file_name, line_number, position)?;
+            file_name_written = true;
+        }
+        if parameters.quiet {
+            return Ok(());
+        }
+        let text = highlight_match(parameters, re, text, span);
+        output.write_all(escape_invalid_utf8(&text).as_bytes())?;
     }
     if file_name_written {
         output.write_all(&"\n".to_string().into_bytes())?;
     }
-    Ok(found_matches)
+    Ok(())
+}
+
+/// The text of the line that the byte at index in text is on. With -w/--whole-files text is
+/// the whole file.
+fn line_at(text: &[u8], index: usize) -> &[u8] {
+    let line_start = text[..index]
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |position| position + 1);
+    let line_end = text[index..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(text.len(), |position| index + position);
+    let line = &text[line_start..line_end];
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// With --format, write the template filled in for the match at span, or for the line
+/// without a match at the start of text with -v/--no-match. The locator is of text, and the
+/// spans are written in order.
+fn write_formatted(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    re: &Regex,
+    file_name: &Option<String>,
+    text: &[u8],
+    locator: &mut Locator,
+    span: Option<&Range<usize>>,
+) -> NedResult<()> {
+    if let Some(ref format) = parameters.format {
+        let index = span.map_or(0, |span| span.start);
+        let unit = parameters.column.unwrap_or(ColumnUnit::Chars);
+        let position = locator.position(index, Some(unit));
+        let fields = Fields {
+            path: file_name.as_deref().unwrap_or(""),
+            line_number: locator.line_number(index),
+            column: position.column.expect("Bug, the column was asked for."),
+            byte_offset: position.byte_offset,
+            line_text: line_at(text, index),
+            captures: span.map(|span| captures_at(re, text, span)),
+        };
+        format.write(output, &fields)?;
+    }
+    Ok(())
+}
+
+/// With --vimgrep, write the line that the byte at index in text is on after its location.
+/// The locator is of text, and the indices are written in order.
+fn write_vimgrep_line(
+    output: &mut dyn Write,
+    parameters: &Parameters,
+    file_name: &Option<String>,
+    text: &[u8],
+    locator: &mut Locator,
+    index: usize,
+) -> NedResult<()> {
+    let line = line_at(text, index);
+    let shown = shown_len(parameters, line);
+    let line_number = locator.line_number(index);
+    let position = shows_position(parameters).then(|| locator.position(index, parameters.column));
+    write_line(
+        output,
+        parameters,
+        file_name,
+        Some(line_number),
+        position,
+        &line[..shown],
+        line.len() - shown,
+    )
 }
 
 /// Taking into account parameters specifying to display or not display file names and line numbers,
@@ -561,6 +1662,7 @@ fn write_file_name_and_line_number(
     parameters: &Parameters,
     file_name: &Option<String>,
     line_number: Option<usize>,
+    position: Option<Position>,
 ) -> NedResult<()> {
     if !parameters.quiet {
         let mut location = "".to_string();
@@ -569,6 +1671,7 @@ fn write_file_name_and_line_number(
                 location.push_str(file_name);
             }
         }
+        // With --count and --count-matches the line number is the count.
         if !parameters.no_line_numbers && !parameters.file_names_only {
             if let Some(line_number) = line_number {
                 if !location.is_empty() {
@@ -577,11 +1680,26 @@ fn write_file_name_and_line_number(
                 location.push_str(&line_number.to_string());
             }
         }
+        if let Some(position) = position.filter(|_| !parameters.file_names_only) {
+            let column = position
+                .column
+                .filter(|_| !parameters.whole_files || parameters.vimgrep);
+            let byte_offset = Some(position.byte_offset).filter(|_| parameters.byte_offset);
+            for number in column.into_iter().chain(byte_offset) {
+                if !location.is_empty() {
+                    location.push(':');
+                }
+                location.push_str(&number.to_string());
+            }
+        }
         if !location.is_empty() {
             location.push_str(
-                if parameters.file_names_only || parameters.line_numbers_only {
+                if parameters.file_names_only || parameters.line_numbers_only || parameters.counts()
+                {
                     "\n"
-                } else if parameters.replace.is_some() || parameters.whole_files {
+                } else if (parameters.replace.is_some() || parameters.whole_files)
+                    && !parameters.vimgrep
+                {
                     ":\n"
                 } else {
                     ":"
@@ -598,48 +1716,60 @@ fn write_file_name_and_line_number(
 
 fn write_newline_if_replaced_text_ends_with_newline(
     output: &mut dyn Write,
-    text: &str,
+    text: &[u8],
 ) -> NedResult<()> {
-    if !text.ends_with('\n') {
+    if !text.ends_with(b"\n") {
         output.write_all(&"\n".to_string().into_bytes())?;
     }
     Ok(())
 }
 
-// TODO: use Cows to reduce allocations in the color*() functions.
-
-fn color_matches_with_number_skip_backwards(
+/// The first shown bytes of the text with the matches at the spans, which are in the order they
+/// are in text, and their groups, highlighted if --colors has been specified.
+fn highlight_matches<'t, 's>(
     parameters: &Parameters,
     re: &Regex,
-    text: &str,
-) -> (String, bool) {
-    let (new_text, found_matches) = replace(
-        parameters,
-        re,
-        text,
-        Red.bold().paint("$0").to_string().as_str(),
-    );
+    text: &'t [u8],
+    shown: usize,
+    spans: impl Iterator<Item = &'s Range<usize>>,
+) -> Cow<'t, [u8]> {
     if parameters.colors {
-        (new_text, found_matches)
+        // Segments past the end of a cut line aren't shown.
+        let segments = spans
+            .take_while(|span| shown == text.len() || span.start < shown)
+            .flat_map(|span| segments(re, text, span))
+            .filter(|(range, _)| shown == text.len() || range.start < shown)
+            .map(|(range, highlight)| (range.start..range.end.min(shown), highlight))
+            .collect::<Vec<Segment>>();
+        paint(text, 0..shown, &segments)
     } else {
-        (text.to_string(), found_matches)
+        Cow::Borrowed(&text[..shown])
     }
 }
 
-fn color_matches_all(parameters: &Parameters, re: &Regex, text: &str) -> String {
+/// The match at the span, with its groups highlighted if --colors has been specified.
+fn highlight_match<'t>(
+    parameters: &Parameters,
+    re: &Regex,
+    text: &'t [u8],
+    span: &Range<usize>,
+) -> Cow<'t, [u8]> {
     if parameters.colors {
-        re.replace_all(text, Red.bold().paint("$0").to_string().as_str())
-            .into_owned()
+        paint(text, span.clone(), &segments(re, text, span))
     } else {
-        text.to_string()
+        Cow::Borrowed(&text[span.clone()])
     }
 }
 
-/// Color the whole text if --colors has been specified.
-fn color(parameters: &Parameters, text: &str) -> String {
-    if parameters.colors {
-        Red.bold().paint(text).to_string()
+/// The segments of the match at the span. Its groups are only looked for when re has some.
+fn segments(re: &Regex, text: &[u8], span: &Range<usize>) -> Vec<Segment> {
+    if re.captures_len() > 1 {
+        let captures = captures_at(re, text, span);
+        match_segments(
+            span,
+            captures.iter().skip(1).flatten().map(|group| group.range()),
+        )
     } else {
-        text.to_string()
+        match_segments(span, iter::empty())
     }
 }
@@ -0,0 +1,73 @@
+//
+// ned, https://github.com/nevdelap/ned, matches.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::parameters::Parameters;
+use regex::bytes::Regex;
+use std::ops::Range;
+
+/// Where the matches of a regex are in a line, or a whole file, found in one pass over it.
+/// Whether it matches, which of the matches --number, --skip, and --backwards select, and
+/// what is colored, written, or replaced, are all decided from them.
+#[derive(Debug, Default)]
+pub struct Matches {
+    spans: Vec<Range<usize>>,
+}
+
+impl Matches {
+    /// The matches of re in text. Unless matching --bytes, text is UTF-8, and empty matches
+    /// that would split a character are left out, as they are when matching strs.
+    pub fn find(parameters: &Parameters, re: &Regex, text: &[u8]) -> Matches {
+        Matches {
+            spans: re
+                .find_iter(text)
+                .map(|_match| _match.range())
+                .filter(|span| !splits_character(parameters, text, span))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// The matches selected by --number, --skip, and --backwards, in the order they are in the
+    /// text.
+    pub fn selected<'m>(
+        &'m self,
+        parameters: &'m Parameters,
+    ) -> impl Iterator<Item = &'m Range<usize>> + 'm {
+        let count = self.spans.len();
+        self.spans
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| parameters.include_match(*index, count))
+            .map(|(_, span)| span)
+    }
+
+    pub fn any_selected(&self, parameters: &Parameters) -> bool {
+        self.selected(parameters).next().is_some()
+    }
+}
+
+fn splits_character(parameters: &Parameters, text: &[u8], span: &Range<usize>) -> bool {
+    !parameters.bytes
+        && span.is_empty()
+        && text.get(span.start).is_some_and(|byte| byte & 0xc0 == 0x80)
+}
@@ -0,0 +1,56 @@
+//
+// ned, https://github.com/nevdelap/ned, mmap.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use std::fs::File;
+
+/// Files this big or bigger are memory mapped when neither --mmap nor --no-mmap is given.
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// Whether files that are searched are memory mapped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mmap {
+    Auto,
+    Always,
+    Never,
+}
+
+/// The file memory mapped, if it is a regular file and --mmap, or its size, says that it
+/// should be. Files that can't be mapped are read instead.
+pub fn map_file(mmap: Mmap, file: &File) -> Option<memmap2::Mmap> {
+    let len = match file.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => return None,
+    };
+    let map = match mmap {
+        Mmap::Auto => len >= MMAP_THRESHOLD,
+        // Empty files can't be mapped.
+        Mmap::Always => len > 0,
+        Mmap::Never => false,
+    };
+    if !map {
+        return None;
+    }
+    // Safety: the map is only read. As with other tools that map files, a file that is
+    // truncated while it is being searched can get the process killed.
+    let mapped = unsafe { memmap2::Mmap::map(file) }.ok()?;
+    #[cfg(unix)]
+    let _ = mapped.advise(memmap2::Advice::Sequential);
+    Some(mapped)
+}
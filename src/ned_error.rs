@@ -19,13 +19,11 @@
 //
 
 use crate::opts::PROGRAM;
-use getopts;
-use glob;
-use regex;
 use std::error;
 use std::fmt;
 use std::io::{self, ErrorKind, Write};
 use std::path;
+use std::str;
 use std::string;
 
 #[derive(Debug)]
@@ -47,7 +45,8 @@ impl error::Error for StringError {
 
 #[derive(Debug)]
 pub enum NedError {
-    FromUtf8(string::FromUtf8Error),
+    Utf8(str::Utf8Error),
+    FromUtf16(string::FromUtf16Error),
     GetOpts(getopts::Fail),
     GlobPattern(glob::PatternError),
     Io(io::Error),
@@ -64,9 +63,15 @@ impl NedError {
     }
 }
 
-impl From<string::FromUtf8Error> for NedError {
-    fn from(err: string::FromUtf8Error) -> NedError {
-        NedError::FromUtf8(err)
+impl From<str::Utf8Error> for NedError {
+    fn from(err: str::Utf8Error) -> NedError {
+        NedError::Utf8(err)
+    }
+}
+
+impl From<string::FromUtf16Error> for NedError {
+    fn from(err: string::FromUtf16Error) -> NedError {
+        NedError::FromUtf16(err)
     }
 }
 
@@ -103,7 +108,8 @@ impl From<String> for NedError {
 impl fmt::Display for NedError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            NedError::FromUtf8(ref err) => write!(f, "{}", err),
+            NedError::Utf8(ref err) => write!(f, "{}", err),
+            NedError::FromUtf16(ref err) => write!(f, "{}", err),
             NedError::GetOpts(ref err) => write!(f, "{}", err),
             NedError::GlobPattern(ref err) => write!(f, "{}", err),
             NedError::Io(ref err) => write!(f, "{}", err),
@@ -116,7 +122,8 @@ impl fmt::Display for NedError {
 impl error::Error for NedError {
     fn cause(&self) -> Option<&dyn error::Error> {
         match *self {
-            NedError::FromUtf8(ref err) => Some(err),
+            NedError::Utf8(ref err) => Some(err),
+            NedError::FromUtf16(ref err) => Some(err),
             NedError::GetOpts(ref err) => Some(err),
             NedError::GlobPattern(ref err) => Some(err),
             NedError::Io(ref err) => Some(err),
@@ -130,20 +137,12 @@ pub type NedResult<T> = Result<T, NedError>;
 
 pub fn stderr_write_err(err: &dyn error::Error) {
     io::stderr()
-        .write_all(&format!("{}: {}\n", PROGRAM, err.to_string()).into_bytes())
+        .write_all(&format!("{}: {}\n", PROGRAM, err).into_bytes())
         .expect("Can't write to stderr!");
 }
 
 pub fn stderr_write_file_err(path_buf: &path::Path, err: &dyn error::Error) {
     io::stderr()
-        .write_all(
-            &format!(
-                "{}: {} {}\n",
-                PROGRAM,
-                path_buf.to_string_lossy(),
-                err.to_string()
-            )
-            .into_bytes(),
-        )
+        .write_all(&format!("{}: {} {}\n", PROGRAM, path_buf.to_string_lossy(), err).into_bytes())
         .expect("Can't write to stderr!");
 }
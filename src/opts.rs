@@ -42,8 +42,16 @@ static POST_DESCRIPTION: &str = "Environment:
 
                         NED_DEFAULTS=\"-u -R --exclude *.bk --exclude-dir .git\"
 Exit codes:
-    0                   matches found/replaced
-    1                   no matches
+    0                   matches found/replaced, or files restored by --undo
+    1                   no matches, or files not restored by --undo
+
+Replacing:
+    Files are replaced by writing a temporary file alongside them and renaming
+    it over the original, so that a file is never left partially written.
+    The new file is given the original's permissions, ownership, and extended
+    attributes. Files with hard links, or whose ownership or extended
+    attributes can't be given to the new file, are instead rewritten in place,
+    and this is reported, as is any metadata that couldn't be preserved.
 
 Quiet:
     When -q/--quiet is specified, ned tests for matches and returns an exit
@@ -74,9 +82,15 @@ pub fn make_opts() -> Options {
         "r",
         "replace",
         "Replace matches. Replacements may include numbered and named groups. Replaces always operate on whole \
-         files.",
+         files, except in stdin with --line-buffered.",
         "REPLACEMENT",
     );
+    opts.optflag(
+        "",
+        "interactive",
+        "Show each match to be replaced, with the lines around it, and ask whether to replace \
+         it. Use -C/--context to set the number of lines, the default is 3.",
+    );
     opts.optflag(
         "w",
         "whole-files",
@@ -104,8 +118,8 @@ pub fn make_opts() -> Options {
     opts.optflag(
         "m",
         "multiline",
-        "Multiline, ^ and $ match the beginning and end of each line. Use with \
-         --whole-files.",
+        "Multiline, ^ and $ match the beginning and end of each line, LF or CRLF terminated \
+         in files with CRLFs. Use with --whole-files.",
     );
     opts.optflag("x", "extended", "Ignore whitespace and # comments.");
     opts.optflag(
@@ -122,6 +136,16 @@ pub fn make_opts() -> Options {
         "Show the match group, specified by number or name.",
         "GROUP",
     );
+    opts.optopt(
+        "",
+        "format",
+        "Show each match as TEMPLATE, on a line of its own. {path}, {line}, {column}, \
+         {byte_offset}, {match}, and {line_text} are where the match is and what it is, and \
+         {N} or {NAME} is a group. {{ and }} are braces, and \\t, \\n, \\r, \\0, and \\\\ \
+         are escapes. With -v/--no-match each line without matches is shown. Takes precedence \
+         over the other options that change what is shown.",
+        "TEMPLATE",
+    );
     opts.optflag("v", "no-match", "Show only non-matching.");
     opts.optflag(
         "f",
@@ -130,6 +154,37 @@ pub fn make_opts() -> Options {
          --no-match to show filenames without matches.",
     );
     opts.optflag("F", "no-filenames", "Don't show filenames.");
+    opts.optflag(
+        "",
+        "json",
+        "Show what is found as JSON Lines, an object on each line for each event, the \
+         begin and end of each file, each matching line with its matches and their groups, \
+         each context line, each replacement, and a summary with the --stats of the run. \
+         Text and paths that aren't UTF-8 are given in base64 as {\"bytes\":\"...\"}. \
+         Takes precedence over the other options that change what is shown. When replacing, \
+         --diff and --stdout show only the events.",
+    );
+    opts.optflag(
+        "",
+        "vimgrep",
+        "Show each match on its own line as FILE:LINE:COLUMN:TEXT, where TEXT is the line \
+         it is on, for the quickfix lists of Vim and Emacs. Is the same with -w/--whole-files, \
+         where LINE and COLUMN are where each match starts. --column=graphemes counts \
+         graphemes. Takes precedence over the other options that change what is shown.",
+    );
+    opts.optflag(
+        "",
+        "count",
+        "Show only the number of lines containing matches in each file, or with -v/\
+         --no-match the number of lines without matches. Files without any are not shown.",
+    );
+    opts.optflag(
+        "",
+        "count-matches",
+        "Show only the number of matches in each file, counting only those selected by \
+         -n/--number, -k/--skip, and -b/--backwards. Files without any are not shown. With \
+         -v/--no-match it is the same as --count.",
+    );
     opts.optflag(
         "l",
         "line-numbers-only",
@@ -142,6 +197,21 @@ pub fn make_opts() -> Options {
         "no-line-numbers",
         "Don't show line numbers. Use without -w/--whole-files.",
     );
+    opts.optflagopt(
+        "",
+        "column",
+        "Show the column of the first match on each line after its line number, or of each \
+         match with -o/--matches-only, counting from 1. UNIT can be chars, the default, or \
+         graphemes to count user-perceived characters. Use without -w/--whole-files.",
+        "UNIT",
+    );
+    opts.optflag(
+        "",
+        "byte-offset",
+        "Show the byte offset within the file of the first match on each line after its line \
+         number, or of each match with -o/--matches-only. With -w/--whole-files it is the \
+         offset of the first match.",
+    );
     opts.optopt(
         "C",
         "context",
@@ -169,10 +239,63 @@ pub fn make_opts() -> Options {
     opts.optmulti("", "include", "Match only files that match GLOB.", "GLOB");
     opts.optmulti("", "exclude", "Skip files matching GLOB.", "GLOB");
     opts.optmulti("", "exclude-dir", "Skip directories matching GLOB.", "GLOB");
+    opts.optopt(
+        "",
+        "binary",
+        "'report' shows 'Binary file FILE matches' for binary files that match rather than \
+         their lines, and reports that they aren't replaced in, 'skip' skips them, and 'text' \
+         treats them as text. Files with a NUL in their first 8K are binary. The default is \
+         'report'.",
+        "WHAT",
+    );
+    opts.optflag(
+        "",
+        "list-skipped-binaries",
+        "With --binary=skip, list the binary files that are skipped on stderr.",
+    );
+    opts.optflag(
+        "",
+        "bytes",
+        "Match the bytes of files rather than decoded text, so that files that aren't \
+         UTF-8 can be searched and replaced in, with everything but the replacements left \
+         byte for byte as it was. Patterns match bytes, so that \\xFF matches the byte FF, \
+         and . matches any byte, and (?u) turns Unicode matching back on. Invalid UTF-8 is \
+         shown escaped as \\xNN. Files are treated as text unless --binary is given.",
+    );
+    opts.optopt(
+        "",
+        "encoding",
+        "Read files that don't start with a byte order mark in ENCODING, such as latin1, \
+         windows-1252, or shift_jis, and write them back in it when replacing, or with 'auto' \
+         read them as UTF-8 if they are, or guess their encoding if they aren't. The default \
+         is UTF-8. Replacing is an error for a file whose encoding cannot represent its \
+         replacements.",
+        "ENCODING",
+    );
+    opts.optopt(
+        "",
+        "max-filesize",
+        "Skip files bigger than SIZE, which is in bytes, or in KiB, MiB, or GiB with a K, M, \
+         or G suffix.",
+        "SIZE",
+    );
+    opts.optflag(
+        "",
+        "list-skipped-big-files",
+        "With --max-filesize, list the files that are skipped on stderr.",
+    );
+    opts.optopt(
+        "",
+        "max-line-length",
+        "Show at most N bytes of each line, followed by how many more bytes it has. Use \
+         without -w/--whole-files.",
+        "N",
+    );
     opts.optflag(
         "u",
         "ignore-non-utf8",
-        "Quietly ignore files that cannot be parsed as UTF-8 (or ASCII). Because \
+        "Quietly ignore files that cannot be parsed as UTF-8 (or ASCII), as UTF-16 \
+         when they start with its byte order mark, or in the --encoding given. Because \
          this requires reading the file, the --exclude option should be preferred.",
     );
     opts.optflag(
@@ -183,7 +306,7 @@ pub fn make_opts() -> Options {
     opts.optflag(
         "c",
         "",
-        "Show filenames, line numbers, and matches in color. Is the same as --colors=always.",
+        "Show filenames, line numbers, and matches in color, with their groups underlined. Is the same as --colors=always.",
     );
     opts.optflagopt(
         "",
@@ -193,6 +316,93 @@ pub fn make_opts() -> Options {
     );
     opts.optflagopt("", "color", "Synonym for --colors.", "WHEN");
     opts.optflag("", "stdout", "Output to stdout.");
+    opts.optflag(
+        "",
+        "line-buffered",
+        "Flush the output after every line, and replace in stdin a line at a time, so that \
+         ned can sit in a pipeline that doesn't end.",
+    );
+    opts.optflag(
+        "",
+        "mmap",
+        "Memory map the files that are searched, rather than reading them. Otherwise only \
+         files of 1MiB or more are memory mapped.",
+    );
+    opts.optflag("", "no-mmap", "Never memory map files.");
+    opts.optopt(
+        "j",
+        "threads",
+        "Process N files at a time. The output is in the same order as when they are \
+         processed one at a time. --interactive processes one file at a time.",
+        "N",
+    );
+    opts.optflagopt(
+        "",
+        "stats",
+        "Show what the run did on stderr when it is done, the files walked, skipped, \
+         matched, and modified, the matches and replacements, the bytes read and written, \
+         and the time taken. 'text' shows a line for each, which is the default, and 'json' \
+         shows them as JSON.",
+        "FORMAT",
+    );
+    opts.optflag(
+        "",
+        "diff",
+        "Show a unified diff of what -r/--replace would change, without changing any files. \
+         Hunks have three lines of context, or the LINES given with -C/--context.",
+    );
+    opts.optflag("", "dry-run", "Synonym for --diff.");
+    opts.optopt(
+        "",
+        "eol",
+        "'preserve' keeps the line endings of files that are replaced in, with new lines \
+         following the file's convention, 'lf' or 'crlf' makes all of their line endings LF or \
+         CRLF. The default is 'preserve'.",
+        "EOL",
+    );
+    opts.optflagopt(
+        "",
+        "backup",
+        "Back up files before replacing in them, to the file name with SUFFIX appended, \
+         .orig by default, or with 'numbered' to FILE.~N~. Only files that are changed are \
+         backed up, and backups are skipped when searching with this option.",
+        "SUFFIX",
+    );
+    opts.optopt(
+        "",
+        "backup-dir",
+        "Back up files to DIR, under the same relative paths as the files, rather than \
+         alongside them. DIR is skipped when searching.",
+        "DIR",
+    );
+    opts.optflag(
+        "",
+        "preserve-mtime",
+        "Keep the modification times of files that are replaced in, so that build systems \
+         don't see them as changed.",
+    );
+    opts.optflag(
+        "",
+        "lock",
+        "Lock files with an advisory lock while they are replaced in, so that other runs of \
+         ned with --lock wait for them. Files that are changed by another process between \
+         being read and written are never written, with or without it.",
+    );
+    opts.optflagopt(
+        "",
+        "journal",
+        "Record the original content of files before replacing in them, so that the run \
+         can be undone with --undo. Runs are recorded in DIR, ~/.local/state/ned/journal \
+         by default.",
+        "DIR",
+    );
+    opts.optflagopt(
+        "",
+        "undo",
+        "Restore the files changed by the last journaled run, or by the run RUN-ID, except \
+         for files that have changed since. Use with --journal when it was given a DIR.",
+        "RUN-ID",
+    );
     opts.optflag(
         "q",
         "quiet",
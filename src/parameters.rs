@@ -20,51 +20,102 @@
 
 extern crate regex;
 
+use crate::backup::Backup;
+use crate::binary::Binary;
 use crate::colors::Colors;
+use crate::encoding::Encoding;
+use crate::eol::Eol;
+use crate::files::FileSize;
+use crate::format::Format;
+use crate::interactive::Interactive;
+use crate::journal::Journal;
+use crate::mmap::Mmap;
 use crate::ned_error::{NedError, NedResult, StringError};
 use crate::options_with_defaults::OptionsWithDefaults;
+use crate::position::ColumnUnit;
+use crate::stats::{Stats, StatsFormat};
 use glob::Pattern;
-use libc;
-use regex::Regex;
+use regex::bytes::{Regex, RegexBuilder};
 use std::collections::HashMap;
 use std::iter::Iterator;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Clone)]
 pub struct Parameters {
     pub all: bool,
+    pub backup: Option<Backup>,
+    pub backup_dir: Option<PathBuf>,
     pub backwards: bool,
+    pub binary: Binary,
+    pub byte_offset: bool,
+    pub bytes: bool,
     pub case_replacements: bool,
+    pub change_context: usize,
     pub colors: bool,
+    pub column: Option<ColumnUnit>,
     pub context_after: usize,
     pub context_before: usize,
+    pub count: bool,
+    pub count_matches: bool,
+    pub crlf_regex: Option<Regex>,
+    pub diff: bool,
+    pub encoding: Encoding,
+    pub eol: Eol,
     pub exclude_dirs: Vec<Pattern>,
     pub excludes: Vec<Pattern>,
     pub file_names_only: bool,
     pub follow: bool,
+    pub format: Option<Format>,
     pub globs: Vec<String>,
     pub group: Option<String>,
     pub help: bool,
     pub ignore_non_utf8: bool,
     pub includes: Vec<Pattern>,
+    pub interactive: Option<Interactive>,
+    pub journal: Option<Journal>,
+    pub json: bool,
+    pub line_buffered: bool,
     pub line_numbers_only: bool,
+    pub list_skipped_big_files: bool,
+    pub list_skipped_binaries: bool,
+    pub lock: bool,
     pub matches_only: bool,
+    pub max_filesize: Option<FileSize>,
+    pub max_line_length: Option<usize>,
+    pub mmap: Mmap,
     pub no_file_names: bool,
     pub no_line_numbers: bool,
     pub no_match: bool,
     pub number: Option<usize>,
+    pub preserve_mtime: bool,
     pub quiet: bool,
     pub recursive: bool,
     pub regex: Option<Regex>,
     pub replace: Option<String>,
     pub skip: usize,
+    pub stats: Option<Stats>,
     pub stdin: bool,
     pub stdout: bool,
+    pub threads: usize,
+    pub undo: Option<String>,
     pub version: bool,
+    pub vimgrep: bool,
     pub whole_files: bool,
 }
 
 impl Parameters {
+    /// Whether files are written, which they are when replacing, but not with --diff or
+    /// --stdout.
+    pub fn writes_files(&self) -> bool {
+        self.replace.is_some() && !self.diff && !self.stdout
+    }
+
+    /// Whether --count or --count-matches shows a count for each file rather than its lines.
+    pub fn counts(&self) -> bool {
+        self.count || self.count_matches
+    }
+
     pub fn limit_matches(&self) -> bool {
         self.skip > 0 || self.number.is_some()
     }
@@ -108,6 +159,36 @@ pub fn get_parameters(options_with_defaults: &OptionsWithDefaults) -> NedResult<
         parse_opt_str(options_with_defaults, "after", Some(0))?.expect("The default is a Some.")
     };
 
+    // Diffs and --interactive show three lines of context unless -C --context is given.
+    let diff =
+        options_with_defaults.opt_present("diff") || options_with_defaults.opt_present("dry-run");
+    let change_context =
+        parse_opt_str(options_with_defaults, "context", Some(3))?.expect("The default is a Some.");
+
+    // --backup-dir on its own backs up files under their own names.
+    let backup_dir = options_with_defaults
+        .opt_str("backup-dir")
+        .map(PathBuf::from);
+    let mut backup = parse_opt_str(options_with_defaults, "backup", None)?;
+    if backup.is_none() && backup_dir.is_some() {
+        backup = Some(Backup::Suffix("".to_string()));
+    }
+
+    // --undo uses the same journal directory that --journal does.
+    let undo = options_with_defaults
+        .opt_present("undo")
+        .then(|| options_with_defaults.opt_str("undo").unwrap_or_default());
+    let journal = if options_with_defaults.opt_present("journal") || undo.is_some() {
+        Some(Journal::new(
+            match options_with_defaults.opt_str("journal") {
+                Some(dir) => PathBuf::from(dir),
+                None => Journal::default_dir()?,
+            },
+        ))
+    } else {
+        None
+    };
+
     let mut exclude_dirs = Vec::<Pattern>::new();
     for exclude in options_with_defaults.opt_strs("exclude-dir") {
         let pattern = Pattern::new(&exclude)?;
@@ -130,17 +211,54 @@ pub fn get_parameters(options_with_defaults: &OptionsWithDefaults) -> NedResult<
 
     // TODO: Test combinations of file name and line number options.
 
+    // --json takes precedence over the options that change what is shown, and over colors.
+    let json = options_with_defaults.opt_present("json");
+    // --format takes precedence over the same options, and --vimgrep, and shows each match
+    // the way its template says.
+    let format: Option<Format> =
+        parse_opt_str(options_with_defaults, "format", None)?.filter(|_| !json);
+    let formats = format.is_some();
+    // --vimgrep takes precedence over the same options, shows file names, line numbers and
+    // columns, and every match on its own line.
+    let vimgrep = !json && !formats && options_with_defaults.opt_present("vimgrep");
+    let count = !json && !formats && !vimgrep && options_with_defaults.opt_present("count");
+    let count_matches =
+        !json && !formats && !vimgrep && options_with_defaults.opt_present("count-matches");
+    if count && count_matches {
+        return Err(NedError::ParameterError(StringError {
+            err: "--count and --count-matches cannot be used together".to_string(),
+        }));
+    }
+
+    // Counts take precedence over file_names_only, line_numbers_only, and no_line_numbers.
     // file_names_only takes precedence over line_numbers_only.
-    let file_names_only = options_with_defaults.opt_present("filenames-only");
-    let line_numbers_only =
-        !whole_files && !file_names_only && options_with_defaults.opt_present("line-numbers-only");
+    let file_names_only = !json
+        && !formats
+        && !vimgrep
+        && !count
+        && !count_matches
+        && options_with_defaults.opt_present("filenames-only");
+    let line_numbers_only = !whole_files
+        && !json
+        && !formats
+        && !vimgrep
+        && !file_names_only
+        && !count
+        && !count_matches
+        && options_with_defaults.opt_present("line-numbers-only");
 
     // file_names_only takes precedence over no_file_names.
-    let no_file_names = !file_names_only && options_with_defaults.opt_present("no-filenames");
-    let no_line_numbers = !line_numbers_only
+    let no_file_names =
+        !vimgrep && !file_names_only && options_with_defaults.opt_present("no-filenames");
+    let no_line_numbers = !vimgrep
+        && !line_numbers_only
+        && !count
+        && !count_matches
         && (file_names_only
             || !whole_files && options_with_defaults.opt_present("no-line-numbers"));
 
+    // --bytes matches bytes rather than characters, so that \xFF is the byte FF.
+    let bytes = options_with_defaults.opt_present("bytes");
     let regex;
     let mut globs = options_with_defaults.free();
 
@@ -152,28 +270,108 @@ pub fn get_parameters(options_with_defaults: &OptionsWithDefaults) -> NedResult<
                  is present.",
             ),
         );
-        regex = Some(Regex::new(&pattern)?);
+        regex = Some(RegexBuilder::new(&pattern).unicode(!bytes).build()?);
     } else if !options_with_defaults.free().is_empty() {
         let pattern = globs.remove(0);
         let pattern = add_regex_flags_to_pattern(options_with_defaults, &pattern);
-        regex = Some(Regex::new(&pattern)?);
+        regex = Some(RegexBuilder::new(&pattern).unicode(!bytes).build()?);
     } else {
         regex = None;
     }
 
+    // The same regex, but with ^ and $ also matching around CRLFs, for files that have them.
+    let crlf_regex = match regex {
+        Some(ref regex) => Some(
+            RegexBuilder::new(regex.as_str())
+                .unicode(!bytes)
+                .crlf(true)
+                .build()?,
+        ),
+        None => None,
+    };
+    if let (Some(format), Some(regex)) = (&format, &regex) {
+        format
+            .check_groups(regex)
+            .map_err(NedError::ParameterError)?;
+    }
+    // --bytes matches files as they are, so they are text unless --binary says otherwise.
+    let binary = parse_opt_str(
+        options_with_defaults,
+        "binary",
+        Some(if bytes { Binary::Text } else { Binary::Report }),
+    )?
+    .expect("The default is a Some.");
+    let encoding = parse_opt_str(options_with_defaults, "encoding", Some(Encoding::Utf8))?
+        .expect("The default is a Some.");
+    let eol = parse_opt_str(options_with_defaults, "eol", Some(Eol::Preserve))?
+        .expect("The default is a Some.");
+
     let number = parse_opt_str(options_with_defaults, "number", None)?;
     let skip =
         parse_opt_str(options_with_defaults, "skip", Some(0))?.expect("The default is a Some.");
 
+    let threads =
+        parse_opt_str(options_with_defaults, "threads", Some(1))?.expect("The default is a Some.");
+    if threads == 0 {
+        return Err(NedError::ParameterError(StringError {
+            err: "invalid value for --threads option".to_string(),
+        }));
+    }
+
+    let max_line_length = parse_opt_str(options_with_defaults, "max-line-length", None)?;
+    if max_line_length == Some(0) {
+        return Err(NedError::ParameterError(StringError {
+            err: "invalid value for --max-line-length option".to_string(),
+        }));
+    }
+
     let stdin = globs.is_empty();
     let stdout = stdin || options_with_defaults.opt_present("stdout");
     let replace = convert_escapes(options_with_defaults.opt_str("replace"));
+    if vimgrep && replace.is_some() {
+        return Err(NedError::ParameterError(StringError {
+            err: "--vimgrep and --replace cannot be used together".to_string(),
+        }));
+    }
+    if formats && replace.is_some() {
+        return Err(NedError::ParameterError(StringError {
+            err: "--format and --replace cannot be used together".to_string(),
+        }));
+    }
+    let mmap = match (
+        options_with_defaults.opt_present("mmap"),
+        options_with_defaults.opt_present("no-mmap"),
+    ) {
+        (true, true) => {
+            return Err(NedError::ParameterError(StringError {
+                err: "--mmap and --no-mmap cannot be used together".to_string(),
+            }));
+        }
+        (true, false) => Mmap::Always,
+        (false, true) => Mmap::Never,
+        (false, false) => Mmap::Auto,
+    };
+    // --interactive asks for answers on stdin, so stdin can't also be the input.
+    let interactive = if options_with_defaults.opt_present("interactive") {
+        if stdin {
+            return Err(NedError::ParameterError(StringError {
+                err: "--interactive cannot be used when reading from stdin".to_string(),
+            }));
+        }
+        Some(Interactive::default())
+    } else {
+        None
+    };
     // TODO: decide what is the best way to deal with STDOUT_FILENO not being defined in the x86_64-pc-windows-gnu,
     // x86_64-pc-windows-msvc, or i686-pc-windows-msvc versions of libc.
     let isatty = unsafe {
         libc::isatty(/*libc::STDOUT_FILENO as i32*/ 1)
     } != 0;
 
+    // The summary of --json has the stats, even when they aren't shown.
+    let stats_format = parse_opt_str::<StatsFormat>(options_with_defaults, "stats", None)?;
+    let stats = (stats_format.is_some() || json).then(|| Stats::new(stats_format));
+
     let c = options_with_defaults.opt_present("c");
     let mut colors = parse_opt_str(options_with_defaults, "colors", None)?;
     if colors.is_none() {
@@ -181,41 +379,82 @@ pub fn get_parameters(options_with_defaults: &OptionsWithDefaults) -> NedResult<
         colors = parse_opt_str(options_with_defaults, "color", Some(Colors::Off))?;
     }
     let colors = colors.expect("The default is a Some.");
-    let colors = c
-        || (colors == Colors::Always && (replace.is_none() || replace.is_some() && stdout)
-            || colors == Colors::Auto && (replace.is_none() || stdout) && isatty)
-            && colors != Colors::Never;
+    let colors = !json
+        && (c
+            || (colors == Colors::Always
+                && (replace.is_none() || replace.is_some() && (stdout || diff))
+                || colors == Colors::Auto && (replace.is_none() || stdout || diff) && isatty)
+                && colors != Colors::Never);
 
     Ok(Parameters {
         all: options_with_defaults.opt_present("all"),
+        backup,
+        backup_dir,
         backwards: options_with_defaults.opt_present("backwards"),
+        binary,
+        byte_offset: !json && !vimgrep && options_with_defaults.opt_present("byte-offset"),
+        bytes,
         case_replacements: options_with_defaults.opt_present("case-replacements"),
+        change_context,
         colors,
+        column: parse_opt_str(
+            options_with_defaults,
+            "column",
+            vimgrep.then_some(ColumnUnit::Chars),
+        )?
+        .filter(|_| !json),
         context_after,
         context_before,
+        count,
+        count_matches,
+        crlf_regex,
+        diff,
+        encoding,
+        eol,
         exclude_dirs,
         excludes,
         file_names_only,
         follow: options_with_defaults.opt_present("follow"),
+        format,
         globs,
-        group: options_with_defaults.opt_str("group"),
+        group: options_with_defaults
+            .opt_str("group")
+            .filter(|_| !json && !formats && !vimgrep),
         help: options_with_defaults.opt_present("help"),
         ignore_non_utf8: options_with_defaults.opt_present("ignore-non-utf8"),
         includes,
+        interactive,
+        journal,
+        json,
+        line_buffered: options_with_defaults.opt_present("line-buffered"),
         line_numbers_only,
-        matches_only: options_with_defaults.opt_present("matches-only"),
+        list_skipped_big_files: options_with_defaults.opt_present("list-skipped-big-files"),
+        list_skipped_binaries: options_with_defaults.opt_present("list-skipped-binaries"),
+        lock: options_with_defaults.opt_present("lock"),
+        matches_only: !json
+            && !formats
+            && !vimgrep
+            && options_with_defaults.opt_present("matches-only"),
+        max_filesize: parse_opt_str(options_with_defaults, "max-filesize", None)?,
+        max_line_length,
+        mmap,
         no_file_names,
         no_line_numbers,
         no_match: options_with_defaults.opt_present("no-match"),
         number,
+        preserve_mtime: options_with_defaults.opt_present("preserve-mtime"),
         quiet: options_with_defaults.opt_present("quiet"),
         recursive: options_with_defaults.opt_present("recursive"),
         regex,
         replace,
         skip,
+        stats,
         stdin,
         stdout,
+        threads,
+        undo,
         version: options_with_defaults.opt_present("version"),
+        vimgrep,
         whole_files,
     })
 }
@@ -0,0 +1,174 @@
+//
+// ned, https://github.com/nevdelap/ned, position.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::encoding::FileEncoding;
+use crate::ned_error::StringError;
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What --column counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnUnit {
+    Chars,
+    Graphemes,
+}
+
+impl FromStr for ColumnUnit {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "chars" => Ok(ColumnUnit::Chars),
+            "graphemes" => Ok(ColumnUnit::Graphemes),
+            _ => Err(StringError {
+                err: format!("invalid column option {}", s),
+            }),
+        }
+    }
+}
+
+/// Where text that is matched starts in its file, and the encoding it was decoded from, so
+/// that byte offsets in the text can be given as byte offsets in the file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileOffset {
+    start: usize,
+    encoding: FileEncoding,
+}
+
+impl FileOffset {
+    /// The start of the text of a file in the encoding, after its byte order mark.
+    pub fn new(encoding: FileEncoding) -> Self {
+        FileOffset {
+            start: encoding.bom_len(),
+            encoding,
+        }
+    }
+
+    /// The byte offset in the file of the start of the text.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The byte offset in the file of the byte at index in text, which starts here.
+    pub fn at(&self, text: &[u8], index: usize) -> usize {
+        self.start + self.encoding.encoded_len(&text[..index])
+    }
+
+    /// Where the text that follows text, which starts here, starts.
+    pub fn after(&self, text: &[u8]) -> Self {
+        FileOffset {
+            start: self.at(text, text.len()),
+            encoding: self.encoding,
+        }
+    }
+}
+
+/// Where a match, or a line without one, is, for --column and --byte-offset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub column: Option<usize>,
+    pub byte_offset: usize,
+}
+
+impl Position {
+    /// The position of the byte at index in text, which starts at byte_offset in its file.
+    pub fn new(
+        text: &[u8],
+        byte_offset: FileOffset,
+        index: usize,
+        unit: Option<ColumnUnit>,
+    ) -> Self {
+        Position {
+            column: unit.map(|unit| column(text, index, unit)),
+            byte_offset: byte_offset.at(text, index),
+        }
+    }
+}
+
+/// Where the bytes of text are, their line numbers and byte offsets in the file, worked out
+/// from one byte to the next as they are asked for in order, so that the matches of a whole
+/// file aren't each counted from its start.
+pub struct Locator<'t> {
+    text: &'t [u8],
+    index: usize,
+    line_number: usize,
+    byte_offset: FileOffset,
+}
+
+impl<'t> Locator<'t> {
+    /// The text is on the line with the line number, or is a whole file when there is none,
+    /// and starts at byte_offset.
+    pub fn new(text: &'t [u8], line_number: Option<usize>, byte_offset: FileOffset) -> Self {
+        Locator {
+            text,
+            index: 0,
+            line_number: line_number.unwrap_or(1),
+            byte_offset,
+        }
+    }
+
+    /// Move on to the byte at index, which is at or after the byte it was at.
+    fn move_to(&mut self, index: usize) {
+        let passed = &self.text[self.index..index];
+        self.line_number += passed.iter().filter(|byte| **byte == b'\n').count();
+        self.byte_offset = self.byte_offset.after(passed);
+        self.index = index;
+    }
+
+    /// The line number of the byte at index.
+    pub fn line_number(&mut self, index: usize) -> usize {
+        self.move_to(index);
+        self.line_number
+    }
+
+    /// The byte offset in the file of the byte at index.
+    pub fn byte_offset(&mut self, index: usize) -> usize {
+        self.move_to(index);
+        self.byte_offset.start()
+    }
+
+    /// The position of the byte at index.
+    pub fn position(&mut self, index: usize, unit: Option<ColumnUnit>) -> Position {
+        Position {
+            column: unit.map(|unit| column(self.text, index, unit)),
+            byte_offset: self.byte_offset(index),
+        }
+    }
+}
+
+/// The column of the byte at index on its line, counting from 1, in characters or graphemes,
+/// with each byte that isn't UTF-8 counted as one.
+pub fn column(text: &[u8], index: usize, unit: ColumnUnit) -> usize {
+    let line_start = text[..index]
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |position| position + 1);
+    text[line_start..index]
+        .utf8_chunks()
+        .map(|chunk| {
+            let valid = match unit {
+                ColumnUnit::Chars => chunk.valid().chars().count(),
+                ColumnUnit::Graphemes => chunk.valid().graphemes(true).count(),
+            };
+            valid + chunk.invalid().len()
+        })
+        .sum::<usize>()
+        + 1
+}
@@ -0,0 +1,322 @@
+//
+// ned, https://github.com/nevdelap/ned, rewrite.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::{NedResult, StringError};
+use std::fs::{self, File, FileTimes, Metadata, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Replace the content of the file at path, which is open as file, by giving a temporary file
+/// in the same directory the original's ownership, permissions, and extended attributes,
+/// writing the content to it, syncing it, and renaming it over the original, so that the
+/// original is never left partially written. Only where that can't be done, because the
+/// temporary file can't be created, the file has hard links that would be separated from it,
+/// its ownership or extended attributes can't be given to the new file, or it can't be renamed
+/// over, is the file instead rewritten in place through file. If writing the new content
+/// fails the original is left as it is. With preserve_mtime the file keeps its original access
+/// and modification times.
+///
+/// Returns what should be reported about the rewrite, that it couldn't be done atomically, or
+/// that some of the file's metadata couldn't be preserved.
+pub fn rewrite_file(
+    path: &Path,
+    file: &mut File,
+    content: &[u8],
+    preserve_mtime: bool,
+) -> NedResult<Vec<StringError>> {
+    // Taken before anything is written, so that it is the original's.
+    let metadata = file.metadata()?;
+    let mut warnings = Vec::new();
+    match rewrite_atomically(path, file, &metadata, content) {
+        Ok(()) => {}
+        Err(AtomicError::Failed(err)) => return Err(err.into()),
+        Err(AtomicError::Impossible(reason)) => {
+            rewrite_in_place(file, content)?;
+            warnings.push(StringError {
+                err: format!(
+                    "could not be replaced atomically ({}), it was rewritten in place",
+                    reason
+                ),
+            });
+            // Writing clears the setuid and setgid bits for anyone but root.
+            if file.metadata()?.permissions() != metadata.permissions() {
+                if let Err(err) = file.set_permissions(metadata.permissions()) {
+                    warnings.push(StringError {
+                        err: format!("its permissions could not be preserved ({})", err),
+                    });
+                }
+            }
+        }
+    }
+    if preserve_mtime {
+        if let Err(err) = restore_times(path, &metadata) {
+            warnings.push(StringError {
+                err: format!("its modification time could not be preserved ({})", err),
+            });
+        }
+    }
+    Ok(warnings)
+}
+
+/// Take an exclusive advisory lock on the file, waiting for any other process that has it,
+/// so that processes that lock it, such as other runs of ned with --lock, don't read it while
+/// it is being replaced in. The lock is released when the file is closed.
+#[cfg(unix)]
+pub fn lock_file(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        // Safety: the file descriptor is open for as long as file is.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn lock_file(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Why a file wasn't replaced atomically.
+enum AtomicError {
+    /// It can't be, and nothing has been written, so it can be rewritten in place instead.
+    Impossible(io::Error),
+    /// The new content couldn't be written, so the original is left as it is.
+    Failed(io::Error),
+}
+
+fn rewrite_atomically(
+    path: &Path,
+    file: &File,
+    metadata: &Metadata,
+    content: &[u8],
+) -> Result<(), AtomicError> {
+    // Rename over the file a symlink points to, not over the symlink.
+    let path = (|| {
+        if fs::symlink_metadata(path)?.file_type().is_symlink() {
+            fs::canonicalize(path)
+        } else {
+            Ok(path.to_path_buf())
+        }
+    })()
+    .map_err(AtomicError::Failed)?;
+    #[cfg(unix)]
+    if metadata.nlink() > 1 {
+        return Err(AtomicError::Impossible(io::Error::other(
+            "it has hard links",
+        )));
+    }
+
+    let (temp_path, mut temp_file) = create_temp_file(&path).map_err(AtomicError::Impossible)?;
+    // The temporary file is only readable by its owner until it has the original's ownership
+    // and permissions, which are given to it before any of the content is written. The
+    // ownership is given first because changing it clears the setuid and setgid bits.
+    let result = (|| {
+        #[cfg(unix)]
+        fchown(&temp_file, Some(metadata.uid()), Some(metadata.gid()))
+            .map_err(AtomicError::Impossible)?;
+        temp_file
+            .set_permissions(metadata.permissions())
+            .map_err(AtomicError::Impossible)?;
+        copy_xattrs(file, &temp_file).map_err(AtomicError::Impossible)?;
+        temp_file.write_all(content).map_err(AtomicError::Failed)?;
+        temp_file.sync_all().map_err(AtomicError::Failed)?;
+        fs::rename(&temp_path, &path).map_err(|err| {
+            // It can't be renamed over when it is on another file system than its directory,
+            // or is a mount point, such as a file bind mounted into a container.
+            if matches!(err.raw_os_error(), Some(libc::EXDEV) | Some(libc::EBUSY)) {
+                AtomicError::Impossible(err)
+            } else {
+                AtomicError::Failed(err)
+            }
+        })
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    sync_parent_dir(&path);
+    Ok(())
+}
+
+fn rewrite_in_place(file: &mut File, content: &[u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(content)?;
+    file.set_len(content.len() as u64)?;
+    file.sync_all()
+}
+
+/// Give the file at path, which may be a new file renamed over the original, the original's
+/// access and modification times.
+fn restore_times(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    OpenOptions::new().write(true).open(path)?.set_times(times)
+}
+
+/// Copy the extended attributes of from to to, which includes ACLs and security labels.
+/// Attributes that to already has with the same value, such as a security label it was
+/// given when it was created, are left as they are so that setting them needn't be allowed.
+#[cfg(target_os = "linux")]
+fn copy_xattrs(from: &File, to: &File) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::io::AsRawFd;
+
+    let names = match xattr_list(from.as_raw_fd()) {
+        Ok(names) => names,
+        // The file system doesn't have them, so there are none to lose.
+        Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for name in names
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+    {
+        let name = CString::new(name).expect("Bug, xattr names don't contain nulls.");
+        let value = xattr_get(from.as_raw_fd(), &name)?;
+        if xattr_get(to.as_raw_fd(), &name).ok().as_ref() == Some(&value) {
+            continue;
+        }
+        // Safety: name is null terminated, and value is valid for its length.
+        let result = unsafe {
+            libc::fsetxattr(
+                to.as_raw_fd(),
+                name.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+            )
+        };
+        if result != 0 {
+            let err = io::Error::last_os_error();
+            return Err(io::Error::new(
+                err.kind(),
+                format!(
+                    "its extended attribute {} could not be copied: {}",
+                    name.to_string_lossy(),
+                    err
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_from: &File, _to: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// The null separated names of the extended attributes of the file open as fd.
+#[cfg(target_os = "linux")]
+fn xattr_list(fd: libc::c_int) -> io::Result<Vec<u8>> {
+    xattr_read(|buffer, size| {
+        // Safety: buffer is valid for size bytes, or null when size is 0.
+        unsafe { libc::flistxattr(fd, buffer as *mut libc::c_char, size) }
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn xattr_get(fd: libc::c_int, name: &std::ffi::CStr) -> io::Result<Vec<u8>> {
+    xattr_read(|buffer, size| {
+        // Safety: name is null terminated, and buffer is valid for size bytes, or null when
+        // size is 0.
+        unsafe { libc::fgetxattr(fd, name.as_ptr(), buffer as *mut libc::c_void, size) }
+    })
+}
+
+/// Call read once to get the size of the value, and again to read it, trying again if it
+/// grew in between.
+#[cfg(target_os = "linux")]
+fn xattr_read(read: impl Fn(*mut u8, usize) -> libc::ssize_t) -> io::Result<Vec<u8>> {
+    loop {
+        let size = read(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buffer = vec![0u8; size as usize];
+        let size = read(buffer.as_mut_ptr(), buffer.len());
+        if size >= 0 {
+            buffer.truncate(size as usize);
+            return Ok(buffer);
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+/// Create a new hidden file alongside path, so that it is on the same file system, and so that
+/// if it is ever left behind it isn't picked up by a later run without -a/--all.
+fn create_temp_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        let temp_path = dir.join(format!(
+            ".{}.ned-{}-{}.tmp",
+            file_name,
+            process::id(),
+            attempt
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // Only readable by its owner until it is given the original's permissions.
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&temp_path) {
+            Ok(temp_file) => return Ok((temp_path, temp_file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Make the rename durable. This is best effort, the file's content is already safe.
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
@@ -0,0 +1,78 @@
+//
+// ned, https://github.com/nevdelap/ned, snapshot.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::{NedError, NedResult, StringError};
+use std::fs::{self, File, Metadata};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// What a file was when it was read, its size, modification time, and on Unix its device and
+/// inode, so that a file that is changed, or replaced, by another process before ned writes
+/// it can be told.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    dev: u64,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl Snapshot {
+    pub fn new(file: &File) -> NedResult<Snapshot> {
+        Ok(Snapshot::from(&file.metadata()?))
+    }
+
+    /// Whether the file at path is still what it was.
+    pub fn is_current(&self, path: &Path) -> bool {
+        match fs::metadata(path) {
+            Ok(metadata) => Snapshot::from(&metadata) == *self,
+            Err(_) => false,
+        }
+    }
+
+    /// An error if the file at path isn't still what it was, so that what another process
+    /// wrote to it isn't lost.
+    pub fn check(&self, path: &Path) -> NedResult<()> {
+        if self.is_current(path) {
+            Ok(())
+        } else {
+            Err(NedError::ParameterError(StringError {
+                err: "has changed since it was read, it was not changed".to_string(),
+            }))
+        }
+    }
+}
+
+impl From<&Metadata> for Snapshot {
+    fn from(metadata: &Metadata) -> Snapshot {
+        Snapshot {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            #[cfg(unix)]
+            dev: metadata.dev(),
+            #[cfg(unix)]
+            ino: metadata.ino(),
+        }
+    }
+}
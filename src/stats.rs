@@ -0,0 +1,182 @@
+//
+// ned, https://github.com/nevdelap/ned, stats.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::{NedResult, StringError};
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// How --stats are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = StringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Ok(StatsFormat::Text),
+            "text" => Ok(StatsFormat::Text),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(StringError {
+                err: format!("invalid stats option {}", s),
+            }),
+        }
+    }
+}
+
+/// The things that --stats counts, in the order they are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Counter {
+    FilesWalked,
+    SkippedHidden,
+    SkippedExcluded,
+    SkippedBinary,
+    SkippedNonUtf8,
+    SkippedTooBig,
+    FilesMatched,
+    Matches,
+    Replacements,
+    FilesModified,
+    BytesRead,
+    BytesWritten,
+}
+
+const COUNTERS: [(Counter, &str, &str); 12] = [
+    (Counter::FilesWalked, "files_walked", "Files walked"),
+    (
+        Counter::SkippedHidden,
+        "skipped_hidden",
+        "Hidden files skipped",
+    ),
+    (
+        Counter::SkippedExcluded,
+        "skipped_excluded",
+        "Excluded files skipped",
+    ),
+    (
+        Counter::SkippedBinary,
+        "skipped_binary",
+        "Binary files skipped",
+    ),
+    (
+        Counter::SkippedNonUtf8,
+        "skipped_non_utf8",
+        "Non-UTF-8 files skipped",
+    ),
+    (
+        Counter::SkippedTooBig,
+        "skipped_too_big",
+        "Too big files skipped",
+    ),
+    (Counter::FilesMatched, "files_matched", "Files matched"),
+    (Counter::Matches, "matches", "Matches"),
+    (Counter::Replacements, "replacements", "Replacements"),
+    (Counter::FilesModified, "files_modified", "Files modified"),
+    (Counter::BytesRead, "bytes_read", "Bytes read"),
+    (Counter::BytesWritten, "bytes_written", "Bytes written"),
+];
+
+/// The counters of --stats, which are shared by clones so that the files processed by each
+/// thread are counted together. They are also counted for the summary of --json, without a
+/// format when --stats isn't given.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    format: Option<StatsFormat>,
+    start: Instant,
+    counters: Arc<[AtomicU64; COUNTERS.len()]>,
+}
+
+impl Stats {
+    pub fn new(format: Option<StatsFormat>) -> Stats {
+        Stats {
+            format,
+            start: Instant::now(),
+            counters: Arc::new(Default::default()),
+        }
+    }
+
+    pub fn add(&self, counter: Counter, count: usize) {
+        self.counters[counter as usize].fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, counter: Counter) -> u64 {
+        self.counters[counter as usize].load(Ordering::Relaxed)
+    }
+
+    /// The reader, counting the bytes read from it.
+    pub fn counting<'r>(&'r self, read: &'r mut dyn Read) -> CountingRead<'r> {
+        CountingRead { read, stats: self }
+    }
+
+    /// Write the counters, and the time since the run started, as a line of JSON, or as a
+    /// line for each, in the --stats format, if there is one.
+    pub fn write(&self, output: &mut dyn Write) -> NedResult<()> {
+        let text = match self.format {
+            Some(StatsFormat::Text) => {
+                let mut text = String::new();
+                for (counter, _, label) in COUNTERS {
+                    text.push_str(&format!("{:<24}{}\n", label, self.get(counter)));
+                }
+                text.push_str(&format!(
+                    "{:<24}{:.3}s\n",
+                    "Elapsed",
+                    self.start.elapsed().as_secs_f64()
+                ));
+                text
+            }
+            Some(StatsFormat::Json) => format!("{}\n", self.to_json()),
+            None => return Ok(()),
+        };
+        output.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// The counters, and the time since the run started, as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut fields = COUNTERS
+            .iter()
+            .map(|(counter, name, _)| format!("\"{}\":{}", name, self.get(*counter)))
+            .collect::<Vec<String>>();
+        fields.push(format!(
+            "\"elapsed_seconds\":{:.3}",
+            self.start.elapsed().as_secs_f64()
+        ));
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+pub struct CountingRead<'r> {
+    read: &'r mut dyn Read,
+    stats: &'r Stats,
+}
+
+impl Read for CountingRead<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let len = self.read.read(buffer)?;
+        self.stats.add(Counter::BytesRead, len);
+        Ok(len)
+    }
+}
@@ -0,0 +1,53 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/binary.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test how binary files are reported, skipped, and not replaced in.
+use crate::tests::{make_test_dir, run};
+use std::fs;
+
+#[test]
+fn binary_replace_is_refused() {
+    let dir = make_test_dir("binary_replace_is_refused");
+    let file = dir.join("file.bin");
+    fs::write(&file, b"one\0two\n").unwrap();
+
+    let exit_code = run(&["two", "--replace", "2", file.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 1);
+    assert_eq!(fs::read(&file).unwrap(), b"one\0two\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn skipped_binaries_are_not_matched_when_listed() {
+    let dir = make_test_dir("skipped_binaries_are_not_matched_when_listed");
+    let file = dir.join("file.bin");
+    fs::write(&file, b"one\0two\n").unwrap();
+
+    for args in [
+        &["--binary=skip"][..],
+        &["--binary=skip", "--list-skipped-binaries"],
+    ] {
+        let mut args = args.to_vec();
+        args.extend(["two", file.to_str().unwrap()]);
+        assert_eq!(run(&args), 1);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,83 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/bytes.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test matching and replacing bytes with --bytes, in files that aren't UTF-8.
+use crate::ned;
+use crate::tests::{make_test_dir, run};
+use std::fs;
+
+#[test]
+fn replace_bytes_leaves_other_bytes_alone() {
+    let dir = make_test_dir("replace_bytes_leaves_other_bytes_alone");
+    let file = dir.join("file.bin");
+    fs::write(&file, b"\xff\xfe dog \x80\ndog\n").unwrap();
+
+    let exit_code = run(&[
+        r"\xFF\xFE dog",
+        "--replace",
+        "cat",
+        "--bytes",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read(&file).unwrap(), b"cat \x80\ndog\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bytes_shows_invalid_utf8_escaped() {
+    let dir = make_test_dir("bytes_shows_invalid_utf8_escaped");
+    let file = dir.join("file.bin");
+    fs::write(&file, b"\xff dog \x80\n").unwrap();
+
+    let mut screen_output: Vec<u8> = vec![];
+    let args = ["dog", "--bytes", "--no-filenames", file.to_str().unwrap()]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let exit_code = ned(&mut screen_output, &args).unwrap();
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        String::from_utf8(screen_output).unwrap(),
+        "1:\\xFF dog \\x80\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bytes_replaces_in_files_with_nuls() {
+    let dir = make_test_dir("bytes_replaces_in_files_with_nuls");
+    let file = dir.join("file.bin");
+    fs::write(&file, b"a\xff\x00b\n").unwrap();
+
+    let exit_code = run(&[
+        r"\xFF\x00",
+        "--replace",
+        "X",
+        "--bytes",
+        file.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read(&file).unwrap(), b"aXb\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,81 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/diff.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test --diff on files on disk.
+use crate::ned;
+use crate::tests::{make_test_dir, run, write_test_file};
+use std::fs;
+
+#[test]
+fn diff_of_read_only_file() {
+    let dir = make_test_dir("diff_of_read_only_file");
+    let file = write_test_file(&dir, "file.txt", "hello\n");
+    let mut permissions = fs::metadata(&file).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&file, permissions.clone()).unwrap();
+
+    let exit_code = run(&[
+        "--diff",
+        "hello",
+        file.to_str().unwrap(),
+        "--replace",
+        "bye",
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "hello\n");
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(&file, permissions).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diff_of_many_changed_lines() {
+    let dir = make_test_dir("diff_of_many_changed_lines");
+    let content = (0..5000)
+        .map(|number| format!("line {}\n", number))
+        .collect::<String>();
+    let file = write_test_file(&dir, "file.txt", &content);
+
+    let mut screen_output: Vec<u8> = vec![];
+    let args = [
+        "--diff",
+        "line",
+        file.to_str().unwrap(),
+        "--replace",
+        "LINE",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect::<Vec<String>>();
+    let exit_code = ned(&mut screen_output, &args).unwrap();
+
+    assert_eq!(exit_code, 0);
+    let screen_output = String::from_utf8(screen_output).unwrap();
+    let lines = screen_output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 3 + 2 * 5000);
+    assert_eq!(lines[2], "@@ -1,5000 +1,5000 @@");
+    assert_eq!(lines[3], "-line 0");
+    assert_eq!(lines[5003], "+LINE 0");
+    assert_eq!(lines[10002], "+LINE 4999");
+    assert_eq!(fs::read_to_string(&file).unwrap(), content);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,172 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/encoding.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test reading and writing files in encodings other than UTF-8 - UTF-16, legacy encodings,
+/// detection, etc.
+use crate::ned;
+use crate::tests::{make_test_dir, run, write_test_file};
+use std::fs;
+
+#[test]
+fn replace_keeps_utf16_encoding() {
+    let dir = make_test_dir("replace_keeps_utf16_encoding");
+    let le_file = dir.join("le.txt");
+    let be_file = dir.join("be.txt");
+    let utf16 = |text: &str, to_bytes: fn(u16) -> [u8; 2]| {
+        "\u{feff}"
+            .encode_utf16()
+            .chain(text.encode_utf16())
+            .flat_map(to_bytes)
+            .collect::<Vec<u8>>()
+    };
+    fs::write(&le_file, utf16("The dog is a dog.\n", u16::to_le_bytes)).unwrap();
+    fs::write(&be_file, utf16("The dog is a dog.\n", u16::to_be_bytes)).unwrap();
+
+    let exit_code = run(&["^The dog", "--replace", "A cat", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        fs::read(&le_file).unwrap(),
+        utf16("A cat is a dog.\n", u16::to_le_bytes)
+    );
+    assert_eq!(
+        fs::read(&be_file).unwrap(),
+        utf16("A cat is a dog.\n", u16::to_be_bytes)
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ignore_invalid_utf16() {
+    let dir = make_test_dir("ignore_invalid_utf16");
+    let file = dir.join("file.txt");
+    // A byte order mark followed by half of a UTF-16 code unit.
+    fs::write(&file, b"\xff\xfed").unwrap();
+
+    let exit_code = run(&["d", "--replace", "c", "-u", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 1);
+    assert_eq!(fs::read(&file).unwrap(), b"\xff\xfed");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_keeps_legacy_encoding() {
+    let dir = make_test_dir("replace_keeps_legacy_encoding");
+    let file = dir.join("file.txt");
+    fs::write(&file, b"The caf\xe9 dog.\n").unwrap();
+
+    let exit_code = run(&[
+        "café dog",
+        "--replace",
+        "café cat",
+        "--encoding",
+        "latin1",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read(&file).unwrap(), b"The caf\xe9 cat.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_with_unrepresentable_characters_leaves_file_alone() {
+    let dir = make_test_dir("replace_with_unrepresentable_characters_leaves_file_alone");
+    let file = dir.join("file.txt");
+    fs::write(&file, b"The caf\xe9 dog.\n").unwrap();
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "犬",
+        "--encoding",
+        "latin1",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 1);
+    assert_eq!(fs::read(&file).unwrap(), b"The caf\xe9 dog.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_detects_encoding() {
+    let dir = make_test_dir("replace_detects_encoding");
+    let shift_jis_file = dir.join("shift_jis.txt");
+    let utf8_file = write_test_file(&dir, "utf8.txt", "犬は犬です。\n");
+    // これは日本語のテキストです。犬は犬です。
+    fs::write(
+        &shift_jis_file,
+        b"\x82\xb1\x82\xea\x82\xcd\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\
+          \x83\x67\x82\xc5\x82\xb7\x81\x42\x8c\xa2\x82\xcd\x8c\xa2\x82\xc5\x82\xb7\x81\x42\x0a",
+    )
+    .unwrap();
+
+    let exit_code = run(&[
+        "犬",
+        "--replace",
+        "猫",
+        "--encoding",
+        "auto",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        fs::read(&shift_jis_file).unwrap(),
+        b"\x82\xb1\x82\xea\x82\xcd\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\
+          \x83\x67\x82\xc5\x82\xb7\x81\x42\x94\x4c\x82\xcd\x94\x4c\x82\xc5\x82\xb7\x81\x42\x0a"
+    );
+    assert_eq!(fs::read_to_string(&utf8_file).unwrap(), "猫は猫です。\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn byte_offsets_are_offsets_in_the_file() {
+    let dir = make_test_dir("byte_offsets_are_offsets_in_the_file");
+    let search = |name: &str, content: &[u8]| {
+        let file = dir.join(name);
+        fs::write(&file, content).unwrap();
+        let mut screen_output: Vec<u8> = vec![];
+        let args = [
+            "x",
+            "--byte-offset",
+            "-o",
+            "--encoding",
+            "latin1",
+            "--no-filenames",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain([file.to_str().unwrap().to_string()])
+        .collect::<Vec<String>>();
+        assert_eq!(ned(&mut screen_output, &args).unwrap(), 0);
+        String::from_utf8(screen_output).unwrap()
+    };
+
+    assert_eq!(search("bom.txt", b"\xef\xbb\xbfab\ncd x\n"), "2:9:x\n");
+    assert_eq!(search("latin1.txt", b"caf\xe9 x\n"), "1:5:x\n");
+    assert_eq!(
+        search("utf16.txt", b"\xff\xfea\x00\n\x00x\x00\n\x00"),
+        "2:6:x\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -20,7 +20,6 @@
 
 /// Just a few general tests. The specifics are tested in the other test files.
 use crate::ned;
-use std;
 
 #[test]
 fn basic_match() {
@@ -58,6 +57,37 @@ fn basic_replace() {
     test(&args, expected_exit_code, &expected_screen_output);
 }
 
+#[test]
+fn basic_replace_diff() {
+    let args = vec!["--diff", "widow", "test", "--replace", "window"];
+    let expected_exit_code = 0;
+    let expected_screen_output = [
+        "--- test/file1.txt
++++ test/file1.txt
+@@ -1,5 +1,5 @@
+-The accidentally ghastly hand plans AN ESCAPE from a cream puff the placid widow. A slovenly
++The accidentally ghastly hand plans AN ESCAPE from a cream puff the placid window. A slovenly
+ onlooker rejoices, because some single-handledly sheepish stalactite knowingly avoids contact with a
+ wisely rhetorical ballerina. Sometimes the waif about a swamp rejoices, but a ruffian always barely
+-befriends an unseemly dilettante! Unlike so many mastadons who have made their lovely widow
++befriends an unseemly dilettante! Unlike so many mastadons who have made their lovely window
+ abhorrent to us, waifs remain womanly.
+\\ No newline at end of file
+",
+        "--- test/longfile.txt
++++ test/longfile.txt
+@@ -12,8 +12,8 @@
+",
+        "-need to remember how hesitantly a bonbon daydreams. A widow somewhat
+-caricatures the widow from a tea party. Nicolas, although somewhat
++need to remember how hesitantly a bonbon daydreams. A window somewhat
++caricatures the window from a tea party. Nicolas, although somewhat
+",
+    ];
+
+    test(&args, expected_exit_code, &expected_screen_output);
+}
+
 #[test]
 fn escape_replace_embedded_real_newline_carriage_return_tab_backslash() {
     let args = vec![
@@ -1480,6 +1510,6 @@ fn fix_output_for_windows(part: &str) -> String {
     // Is sufficient for current requirements as I make tests
     // written on Linux work on Windows. Future tests will
     // be written to work on both platforms .
-    part.replace("/", &std::path::MAIN_SEPARATOR.to_string())
+    part.replace("/", std::path::MAIN_SEPARATOR_STR)
         .replace("\r", "")
 }
@@ -0,0 +1,201 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/interactive.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test --interactive replacing, with the answers given as input.
+use crate::interactive::Interactive;
+use crate::options_with_defaults::OptionsWithDefaults;
+use crate::opts::make_opts;
+use crate::parameters::get_parameters;
+use crate::replace_interactively;
+use std::env;
+use std::io::Cursor;
+
+#[test]
+fn interactive_yes_and_no() {
+    let input = "\
+one dog
+two dog
+three dog
+";
+    let answers = "y\nn\ny\n";
+    let expected_prompt = "\
+bogus_file.txt:1:
+one [dog -> cat]
+two dog
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: bogus_file.txt:2:
+one dog
+two [dog -> cat]
+three dog
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: bogus_file.txt:3:
+two dog
+three [dog -> cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: ";
+    let expected_output = "\
+one cat
+two dog
+three cat
+";
+
+    test(
+        input,
+        "dog",
+        "--replace=cat -C 1",
+        answers,
+        true,
+        expected_prompt,
+        expected_output,
+        false,
+    );
+}
+
+#[test]
+fn interactive_all() {
+    let input = "\
+one dog
+two dog
+three dog
+";
+    let answers = "n\na\n";
+    let expected_prompt = "\
+bogus_file.txt:1:
+one [dog -> cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: bogus_file.txt:2:
+two [dog -> cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: ";
+    let expected_output = "\
+one dog
+two cat
+three cat
+";
+
+    test(
+        input,
+        "dog",
+        "--replace=cat -C 0",
+        answers,
+        true,
+        expected_prompt,
+        expected_output,
+        false,
+    );
+}
+
+#[test]
+fn interactive_quit() {
+    let input = "\
+one dog
+two dog
+three dog
+";
+    let answers = "y\nwhat?\nq\n";
+    let expected_prompt = "\
+bogus_file.txt:1:
+one [dog -> cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: bogus_file.txt:2:
+two [dog -> cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: \
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: ";
+    let expected_output = "\
+one cat
+two dog
+three dog
+";
+
+    test(
+        input,
+        "dog",
+        "--replace=cat -C 0",
+        answers,
+        true,
+        expected_prompt,
+        expected_output,
+        true,
+    );
+}
+
+#[test]
+fn interactive_only_asks_about_selected_matches() {
+    let input = "one dog two dog three dog four dog\n";
+    let answers = "n\ny\n";
+    let expected_prompt = "\
+bogus_file.txt:1:
+one dog two dog [three dog -> three_cat] four dog
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: bogus_file.txt:1:
+one dog two dog three dog [four dog -> four_cat]
+Replace? (y)es, (n)o, (a)ll in file, (q)uit: ";
+    let expected_output = "one dog two dog three dog four_cat\n";
+
+    test(
+        input,
+        "([a-z]+) dog",
+        "--replace=${1}_cat --number 2 --backwards -C 0",
+        answers,
+        true,
+        expected_prompt,
+        expected_output,
+        false,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn test(
+    input: &str,
+    pattern: &str,
+    args: &str,
+    answers: &str,
+    expected_found_matches: bool,
+    expected_prompt: &str,
+    expected_output: &str,
+    expected_quit: bool,
+) {
+    let mut args = args
+        .split_whitespace()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    args.insert(0, pattern.to_string());
+    // The dummy glob argument prevents it from reading stdin.
+    args.push("--interactive".to_string());
+    args.push("dummy".to_string());
+    unsafe { env::set_var("NED_DEFAULTS", "") };
+    let options_with_defaults = OptionsWithDefaults::new(make_opts(), &args).unwrap();
+    let parameters = get_parameters(&options_with_defaults).unwrap();
+    let re = parameters.regex.clone().unwrap();
+    let replace = parameters.replace.clone().unwrap();
+    let interactive = parameters.interactive.clone().unwrap();
+
+    let mut prompt: Vec<u8> = vec![];
+    let mut answers = Cursor::new(answers.to_string().into_bytes());
+    let (output, spans) = replace_interactively(
+        &parameters,
+        &interactive,
+        &re,
+        &Some("bogus_file.txt".to_string()),
+        input.as_bytes(),
+        replace.as_bytes(),
+        &mut prompt,
+        &mut answers,
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(prompt).unwrap(), expected_prompt);
+    assert_eq!(String::from_utf8(output).unwrap(), expected_output);
+    assert_eq!(!spans.is_empty(), expected_found_matches);
+    assert_eq!(Interactive::has_quit(&interactive), expected_quit);
+}
@@ -0,0 +1,109 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/json.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test the events that --json shows.
+use crate::ned;
+use crate::tests::{make_test_dir, write_test_file};
+use std::fs;
+
+#[test]
+fn json_shows_events() {
+    let dir = make_test_dir("json_shows_events");
+    let file = dir.join("file.txt");
+    fs::write(&file, b"The cat.\nThe \xff dog.\n").unwrap();
+    let path = file.to_str().unwrap();
+
+    let mut screen_output: Vec<u8> = vec![];
+    let args = ["(?P<animal>dog)", "--json", "--bytes", "-B", "1", path]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let exit_code = ned(&mut screen_output, &args).unwrap();
+
+    assert_eq!(exit_code, 0);
+    let screen_output = String::from_utf8(screen_output).unwrap();
+    let lines = screen_output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        format!("{{\"type\":\"begin\",\"path\":\"{}\"}}", path)
+    );
+    assert_eq!(
+        lines[1],
+        format!(
+            "{{\"type\":\"context\",\"path\":\"{}\",\"line_number\":1,\"byte_offset\":0,\
+             \"text\":\"The cat.\"}}",
+            path
+        )
+    );
+    assert_eq!(
+        lines[2],
+        format!(
+            "{{\"type\":\"match\",\"path\":\"{}\",\"line_number\":2,\"byte_offset\":9,\
+             \"text\":{{\"bytes\":\"VGhlIP8gZG9nLg==\"}},\"matches\":[{{\"start\":6,\"end\":9,\
+             \"column\":7,\"text\":\"dog\",\"groups\":[{{\"index\":1,\"name\":\"animal\",\
+             \"start\":6,\"end\":9,\"text\":\"dog\"}}]}}]}}",
+            path
+        )
+    );
+    assert_eq!(
+        lines[3],
+        format!(
+            "{{\"type\":\"end\",\"path\":\"{}\",\"found_matches\":true}}",
+            path
+        )
+    );
+    assert!(lines[4].starts_with("{\"type\":\"summary\",\"stats\":{\"files_walked\":1,"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn json_shows_replacements() {
+    let dir = make_test_dir("json_shows_replacements");
+    let file = write_test_file(&dir, "file.txt", "The cat.\nThe dog is a dog.\n");
+    let path = file.to_str().unwrap();
+
+    let mut screen_output: Vec<u8> = vec![];
+    let args = ["dog", "--replace=big $0", "--json", path]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let exit_code = ned(&mut screen_output, &args).unwrap();
+
+    assert_eq!(exit_code, 0);
+    let screen_output = String::from_utf8(screen_output).unwrap();
+    let lines = screen_output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 5);
+    for (line, start, end) in [(lines[1], 13, 16), (lines[2], 22, 25)] {
+        assert_eq!(
+            line,
+            format!(
+                "{{\"type\":\"replace\",\"path\":\"{}\",\"line_number\":2,\"start\":{},\
+                 \"end\":{},\"old\":\"dog\",\"new\":\"big dog\"}}",
+                path, start, end
+            )
+        );
+    }
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "The cat.\nThe big dog is a big dog.\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
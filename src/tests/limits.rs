@@ -0,0 +1,89 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/limits.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test the limits on what is searched and shown - --max-filesize, etc.
+use crate::ned;
+use crate::rewrite::lock_file;
+use crate::tests::{make_test_dir, run, write_test_file};
+use std::fs;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn files_bigger_than_max_filesize_are_skipped() {
+    let dir = make_test_dir("files_bigger_than_max_filesize_are_skipped");
+    write_test_file(&dir, "big.txt", &"The dog.\n".repeat(200));
+    let small = write_test_file(&dir, "small.txt", "The dog.\n");
+
+    let mut screen_output: Vec<u8> = vec![];
+    let args = ["dog", "--max-filesize", "1K", dir.to_str().unwrap()]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let exit_code = ned(&mut screen_output, &args).unwrap();
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        String::from_utf8(screen_output).unwrap(),
+        format!("{}:1:The dog.\n", small.to_str().unwrap())
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_bigger_than_max_filesize_are_not_opened() {
+    let dir = make_test_dir("files_bigger_than_max_filesize_are_not_opened");
+    let big = write_test_file(&dir, "big.txt", &"The dog.\n".repeat(200));
+    let held = fs::File::open(&big).unwrap();
+    lock_file(&held).unwrap();
+
+    // The replace would wait for the lock if the file were opened.
+    let (sender, receiver) = mpsc::channel();
+    let path = dir.clone();
+    thread::spawn(move || {
+        let args = [
+            "dog",
+            "--replace",
+            "cat",
+            "--lock",
+            "--max-filesize",
+            "1K",
+            "--list-skipped-big-files",
+            path.to_str().unwrap(),
+        ];
+        sender.send(run(&args)).unwrap();
+    });
+
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(10)), Ok(1));
+    drop(held);
+    assert_eq!(fs::read_to_string(&big).unwrap(), "The dog.\n".repeat(200));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn max_filesize_must_be_a_size() {
+    let args = ["dog", "--max-filesize", "1X", "file.txt"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+
+    assert!(ned(&mut vec![], &args).is_err());
+}
@@ -0,0 +1,51 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/lines.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test searching lines as they are read.
+use crate::ned;
+use crate::tests::make_test_dir;
+use std::fs;
+
+#[test]
+fn nothing_is_shown_of_files_that_are_not_utf8() {
+    let dir = make_test_dir("nothing_is_shown_of_files_that_are_not_utf8");
+    let file = dir.join("file.txt");
+    fs::write(&file, b"foo one\nbar\n\xff\xfe foo\n").unwrap();
+
+    for args in [
+        vec!["foo", "--mmap"],
+        vec!["foo", "--no-mmap"],
+        vec!["foo", "--mmap", "-u"],
+        vec!["foo", "--no-mmap", "-u"],
+        vec!["foo", "--no-mmap", "-q"],
+    ] {
+        let mut screen_output: Vec<u8> = vec![];
+        let args = args
+            .iter()
+            .chain([dir.to_str().unwrap()].iter())
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+        let exit_code = ned(&mut screen_output, &args).unwrap();
+
+        assert_eq!(exit_code, 1, "{:?}", args);
+        assert_eq!(String::from_utf8(screen_output).unwrap(), "", "{:?}", args);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
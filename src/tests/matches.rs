@@ -1643,6 +1643,1084 @@ related to the hand to an ungodly dahlia.
     );
 }
 
+#[test]
+fn diff_replace_quiet_and_not_quiet() {
+    let input = "\
+one
+two
+three
+four
+five
+six
+seven
+eight
+nine
+ten
+eleven
+";
+    let pattern = "^(two|ten)$";
+    let args = "--diff -m --replace=${1}!";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+--- bogus_file.txt
++++ bogus_file.txt
+@@ -1,5 +1,5 @@
+ one
+-two
++two!
+ three
+ four
+ five
+@@ -7,5 +7,5 @@
+ seven
+ eight
+ nine
+-ten
++ten!
+ eleven
+";
+    let expected_file_content = &input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn diff_replace_merged_hunk_quiet_and_not_quiet() {
+    let input = "\
+one
+two
+three
+four
+five
+six
+";
+    let pattern = "^(two|five)$";
+    let args = "--dry-run -m -C 1 --replace=${1}!";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+--- bogus_file.txt
++++ bogus_file.txt
+@@ -1,6 +1,6 @@
+ one
+-two
++two!
+ three
+ four
+-five
++five!
+ six
+";
+    let expected_file_content = &input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn diff_replace_added_and_removed_lines_quiet_and_not_quiet() {
+    let input = "\
+one
+two
+three";
+    let pattern = "two\n";
+    let args = "--diff -C 0 --replace=2\\n2\\n";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+--- bogus_file.txt
++++ bogus_file.txt
+@@ -2 +2,2 @@
+-two
++2
++2
+";
+    let expected_file_content = &input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn diff_replace_no_newline_at_end_of_file_quiet_and_not_quiet() {
+    let input = "\
+one
+two";
+    let pattern = "two";
+    let args = "--diff --replace=2";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+--- bogus_file.txt
++++ bogus_file.txt
+@@ -1,2 +1,2 @@
+ one
+-two
+\\ No newline at end of file
++2
+\\ No newline at end of file
+";
+    let expected_file_content = &input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn diff_replace_no_match_quiet_and_not_quiet() {
+    let input = "\
+one
+two
+";
+    let pattern = "three";
+    let args = "--diff --replace=3";
+    let expected_found_matches = false;
+    let expected_screen_output = "";
+    let expected_file_content = &input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn crlf_line_anchors_quiet_and_not_quiet() {
+    let input = "one\r\ntwo\r\n";
+    let pattern = "^(t.*)$";
+    let args = "-m --replace=[$1]";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "one\r\n[two]\r\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn crlf_line_match_quiet_and_not_quiet() {
+    let input = "one\r\ntwo\r\nthree\r\n";
+    let pattern = "two$";
+    let args = "";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:2:two\n";
+    let expected_file_content = "one\r\ntwo\r\nthree\r\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn crlf_replacement_line_endings_follow_file_quiet_and_not_quiet() {
+    let input = "one\r\ntwo\r\nthree\n";
+    let pattern = "one";
+    let args = "--replace=1\\n1";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "1\r\n1\r\ntwo\r\nthree\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn lf_replacement_line_endings_follow_file_quiet_and_not_quiet() {
+    let input = "one\ntwo\r\nthree\n";
+    let pattern = "one";
+    let args = "--replace=1\\n1";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "1\n1\ntwo\r\nthree\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn eol_lf_quiet_and_not_quiet() {
+    let input = "one\r\ntwo\nthree\r\n";
+    let pattern = "two";
+    let args = "--eol=lf --replace=2";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "one\n2\nthree\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn eol_crlf_quiet_and_not_quiet() {
+    let input = "one\r\ntwo\nthree\n";
+    let pattern = "two";
+    let args = "--eol=crlf --replace=2\\n2";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "one\r\n2\r\n2\r\nthree\r\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn utf8_bom_is_not_content_quiet_and_not_quiet() {
+    let input = "\u{feff}one\ntwo\n";
+    let pattern = "^one";
+    let args = "";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:one\n";
+    let expected_file_content = "\u{feff}one\ntwo\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn utf8_bom_kept_on_replace_quiet_and_not_quiet() {
+    let input = "\u{feff}one\ntwo\n";
+    let pattern = "^one";
+    let args = "--replace=1";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "\u{feff}1\ntwo\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_report_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "two";
+    let args = "";
+    let expected_found_matches = true;
+    let expected_screen_output = "Binary file bogus_file.txt matches\n";
+    let expected_file_content = "one\0two\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_report_no_match_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "three";
+    let args = "";
+    let expected_found_matches = false;
+    let expected_screen_output = "";
+    let expected_file_content = "one\0two\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_report_file_names_only_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "two";
+    let args = "-f";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt\n";
+    let expected_file_content = "one\0two\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_skip_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "two";
+    let args = "--binary=skip";
+    let expected_found_matches = false;
+    let expected_screen_output = "";
+    let expected_file_content = "one\0two\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_text_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "two";
+    let args = "--binary=text";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:one\0two\n";
+    let expected_file_content = "one\0two\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn binary_text_replace_quiet_and_not_quiet() {
+    let input = "one\0two\n";
+    let pattern = "two";
+    let args = "--binary=text --replace=2";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "one\x002\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn bytes_dot_matches_a_byte_quiet_and_not_quiet() {
+    let input = "café\n";
+    let pattern = "^caf..$";
+    let args = "--bytes";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:café\n";
+    let expected_file_content = "café\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn text_dot_matches_a_character_quiet_and_not_quiet() {
+    let input = "café\n";
+    let pattern = "^caf..$";
+    let args = "";
+    let expected_found_matches = false;
+    let expected_screen_output = "";
+    let expected_file_content = "café\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn bytes_replace_quiet_and_not_quiet() {
+    let input = "café\n";
+    let pattern = "\\xC3\\xA9";
+    let args = "--bytes --replace=e";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "cafe\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn empty_matches_do_not_split_characters_quiet_and_not_quiet() {
+    let input = "é\n";
+    let pattern = "x*";
+    let args = "--replace=-";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "-é-\n-";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn context_before_and_after_overlapping_quiet_and_not_quiet() {
+    let input = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\n";
+    let pattern = "four|six";
+    let args = "-B 2 -A 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+bogus_file.txt:2:two
+bogus_file.txt:3:three
+bogus_file.txt:4:four
+bogus_file.txt:5:five
+bogus_file.txt:6:six
+bogus_file.txt:7:seven
+";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn context_across_the_first_block_quiet_and_not_quiet() {
+    // Line 923 starts in the first 8192 bytes, which are read on their own, and ends after them.
+    let input = (1..=1000)
+        .map(|number| format!("line {}\n", number))
+        .collect::<String>();
+    let pattern = "line 923";
+    let args = "-C 2";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+bogus_file.txt:921:line 921
+bogus_file.txt:922:line 922
+bogus_file.txt:923:line 923
+bogus_file.txt:924:line 924
+bogus_file.txt:925:line 925
+";
+    let expected_file_content = &input;
+
+    test(
+        &input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn group_with_skip_and_number_quiet_and_not_quiet() {
+    let input = "a1 b2 c3\nd4\n";
+    let pattern = "(\\w)(\\d)";
+    let args = "--group 2 --skip 1 --number 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:2\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn replace_with_and_without_groups_quiet_and_not_quiet() {
+    let input = "a1 b2 c3\n";
+    let pattern = "(\\w)(\\d)";
+    let args = "--replace=$2$1 --backwards --number 2";
+    let expected_found_matches = true;
+    let expected_screen_output = "";
+    let expected_file_content = "a1 2b 3c\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+
+    let args = "--replace=- --skip 1";
+    let expected_file_content = "a1 - -\n";
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn colored_groups_quiet_and_not_quiet() {
+    let input = "The dog dog\n";
+    let pattern = "T(he) (d)og";
+    let args = "--colors=always";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+\u{1b}[35mbogus_file.txt:1:\u{1b}[0m\u{1b}[1;31mT\u{1b}[0m\u{1b}[1;4;31mhe\u{1b}[0m\u{1b}[1;31m \
+\u{1b}[0m\u{1b}[1;4;31md\u{1b}[0m\u{1b}[1;31mog\u{1b}[0m dog
+";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn colored_matches_only_with_groups_quiet_and_not_quiet() {
+    let input = "The dog dog\n";
+    let pattern = "(d)og";
+    let args = "--colors=always --matches-only";
+    let expected_found_matches = true;
+    let expected_screen_output = "\
+\u{1b}[35mbogus_file.txt:1:\u{1b}[0m\u{1b}[1;4;31md\u{1b}[0m\u{1b}[1;31mog\u{1b}[0m\
+\u{1b}[1;4;31md\u{1b}[0m\u{1b}[1;31mog\u{1b}[0m
+";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn colored_group_is_highlighted_whole_quiet_and_not_quiet() {
+    // The group doesn't match the pattern on its own, but it is all highlighted.
+    let input = "aab\n";
+    let pattern = "(a+)b";
+    let args = "--colors=always --group 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "\u{1b}[35mbogus_file.txt:1:\u{1b}[0m\u{1b}[1;31maa\u{1b}[0m\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn long_lines_are_cut_quiet_and_not_quiet() {
+    // The line is cut before the é rather than in it.
+    let input = "short dog\nthe dog has a long café dog line\n";
+    let pattern = "dog";
+    let args = "--max-line-length 23";
+    let expected_found_matches = true;
+    let expected_screen_output =
+        "bogus_file.txt:1:short dog\nbogus_file.txt:2:the dog has a long caf [... 11 more bytes]\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn colored_long_lines_are_cut_in_the_match_quiet_and_not_quiet() {
+    let input = "the dog is a dog\n";
+    let pattern = "dog";
+    let args = "--colors=always --max-line-length 6";
+    let expected_found_matches = true;
+    let expected_screen_output =
+        "\u{1b}[35mbogus_file.txt:1:\u{1b}[0mthe \u{1b}[1;31mdo\u{1b}[0m [... 10 more bytes]\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn count_lines_with_matches_quiet_and_not_quiet() {
+    let input = "dog dog\ncat\ndog\n";
+    let pattern = "dog";
+    let args = "--count";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:2\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn count_lines_without_matches_quiet_and_not_quiet() {
+    let input = "dog dog\ncat\ndog\n";
+    let pattern = "dog";
+    let args = "--count -v";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn count_selected_matches_quiet_and_not_quiet() {
+    let input = "dog dog dog\ncat\ndog\n";
+    let pattern = "dog";
+    let args = "--count-matches --number 2";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:3\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn count_matches_in_whole_files_quiet_and_not_quiet() {
+    let input = "dog dog dog\ncat\ndog\n";
+    let pattern = "dog";
+    let args = "--count-matches --whole-files --skip 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:3\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn count_nothing_is_not_shown_quiet_and_not_quiet() {
+    let input = "cat\n";
+    let pattern = "dog";
+    let args = "--count";
+    let expected_found_matches = false;
+    let expected_screen_output = "";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+#[test]
+fn column_of_first_match_quiet_and_not_quiet() {
+    let input = "one dog\ncafé dog dog\ncat\n";
+    let pattern = "dog";
+    let args = "--column";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:5:one dog\nbogus_file.txt:2:6:café dog dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn column_in_graphemes_quiet_and_not_quiet() {
+    let input = "e\u{301} dog\n";
+    let pattern = "dog";
+    let args = "--column=graphemes";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:3:e\u{301} dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn column_and_byte_offset_of_lines_without_matches_quiet_and_not_quiet() {
+    let input = "dog\ncat\n";
+    let pattern = "dog";
+    let args = "--column --byte-offset -v";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:2:1:4:cat\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn byte_offset_of_each_match_quiet_and_not_quiet() {
+    let input = "one dog\ncafé dog dog\n";
+    let pattern = "dog";
+    let args = "--byte-offset -o";
+    let expected_found_matches = true;
+    let expected_screen_output =
+        "bogus_file.txt:1:4:dog\nbogus_file.txt:2:14:dog\nbogus_file.txt:2:18:dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn byte_offset_in_whole_files_quiet_and_not_quiet() {
+    let input = "one\ncat dog\n";
+    let pattern = "dog";
+    let args = "--byte-offset --column -w";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:8:\none\ncat dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+#[test]
+fn vimgrep_shows_each_match_quiet_and_not_quiet() {
+    let input = "one dog\ncafé dog dog\r\ncat\n";
+    let pattern = "dog";
+    let args = "--vimgrep -o -F";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:1:5:one dog\nbogus_file.txt:2:6:café dog dog\nbogus_file.txt:2:10:café dog dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn vimgrep_in_whole_files_quiet_and_not_quiet() {
+    let input = "one\ncat dog\ndog\n";
+    let pattern = "dog";
+    let args = "--vimgrep -w";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:2:5:cat dog\nbogus_file.txt:3:1:dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn vimgrep_lines_without_matches_quiet_and_not_quiet() {
+    let input = "dog\ncat\n";
+    let pattern = "dog";
+    let args = "--vimgrep -v -C 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:2:1:cat\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+#[test]
+fn format_shows_groups_as_tsv_quiet_and_not_quiet() {
+    let input = "a=1 b=2\nnothing\nc=3\n";
+    let pattern = "(\\w)=(?P<value>\\d)";
+    let args = "--format {line}:{column}\\t{1}\\t{value}\\t{2}\\t{{{match}}}";
+    let expected_found_matches = true;
+    let expected_screen_output = "1:1\ta\t1\t1\t{a=1}\n1:5\tb\t2\t2\t{b=2}\n3:1\tc\t3\t3\t{c=3}\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn format_shows_the_path_offset_and_line_text_quiet_and_not_quiet() {
+    let input = "one dog\ncafé dog dog\n";
+    let pattern = "dog";
+    let args = "--format {path}:{byte_offset}:{line_text}";
+    let expected_found_matches = true;
+    let expected_screen_output = "bogus_file.txt:4:one dog\nbogus_file.txt:14:café dog dog\nbogus_file.txt:18:café dog dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn format_shows_lines_without_matches_quiet_and_not_quiet() {
+    let input = "dog\ncat\n";
+    let pattern = "dog";
+    let args = "--format {line}:{match}:{line_text} -v -C 1";
+    let expected_found_matches = true;
+    let expected_screen_output = "2::cat\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn vimgrep_of_many_matches_in_whole_files_quiet_and_not_quiet() {
+    let input = "dog dog\ncat\nx dog\n";
+    let pattern = "dog";
+    let args = "--vimgrep -w";
+    let expected_found_matches = true;
+    let expected_screen_output =
+        "bogus_file.txt:1:1:dog dog\nbogus_file.txt:1:5:dog dog\nbogus_file.txt:3:3:x dog\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn format_of_many_matches_in_whole_files_quiet_and_not_quiet() {
+    let input = "dog dog\ncat\nx dog\n";
+    let pattern = "dog";
+    let args = "--format {line}:{column}:{byte_offset} -w";
+    let expected_found_matches = true;
+    let expected_screen_output = "1:1:0\n1:5:4\n3:3:14\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
+#[test]
+fn format_in_whole_files_quiet_and_not_quiet() {
+    let input = "one\ncat dog\ndog\n";
+    let pattern = "dog";
+    let args = "--format {line}:{column}:{line_text}\\0 -w";
+    let expected_found_matches = true;
+    let expected_screen_output = "2:5:cat dog\u{0}\n3:1:dog\u{0}\n";
+    let expected_file_content = input;
+
+    test(
+        input,
+        pattern,
+        args,
+        expected_found_matches,
+        expected_screen_output,
+        expected_file_content,
+    );
+}
+
 fn test(
     input: &str,
     pattern: &str,
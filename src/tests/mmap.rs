@@ -0,0 +1,70 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/mmap.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test memory mapping files with --mmap and --no-mmap.
+use crate::ned;
+use crate::tests::{make_test_dir, write_test_file};
+use std::fs;
+
+#[test]
+fn mapped_and_read_files_are_searched_the_same() {
+    let dir = make_test_dir("mapped_and_read_files_are_searched_the_same");
+    let file = write_test_file(
+        &dir,
+        "file.txt",
+        "\u{feff}The dog\r\nis\r\na dog\r\nand\r\nnot a cat\r\n",
+    );
+
+    let search = |args: &[&str]| {
+        let mut screen_output: Vec<u8> = vec![];
+        let args = args
+            .iter()
+            .chain([file.to_str().unwrap()].iter())
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+        let exit_code = ned(&mut screen_output, &args).unwrap();
+        (exit_code, String::from_utf8(screen_output).unwrap())
+    };
+    for args in [
+        vec!["dog$", "-C", "1", "--no-filenames"],
+        vec!["dog", "-w", "-o"],
+        vec!["cat", "-f"],
+    ] {
+        let mapped = search(&[args.as_slice(), &["--mmap"]].concat());
+        let read = search(&[args.as_slice(), &["--no-mmap"]].concat());
+        assert_eq!(mapped, read);
+        assert_eq!(mapped.0, 0);
+    }
+    assert_eq!(
+        search(&["dog$", "-C", "1", "--no-filenames", "--mmap"]).1,
+        "1:The dog\n2:is\n3:a dog\n4:and\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mmap_and_no_mmap_cannot_be_used_together() {
+    let args = ["dog", "--mmap", "--no-mmap", "file.txt"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+
+    assert!(ned(&mut vec![], &args).is_err());
+}
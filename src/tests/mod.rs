@@ -18,7 +18,61 @@
 // 02110-1301, USA.
 //
 
+mod binary;
+mod bytes;
+mod diff;
+mod encoding;
 mod files;
 mod general;
+mod interactive;
+mod json;
+mod limits;
+mod lines;
 mod matches;
+mod mmap;
 mod parameters;
+mod rewrite;
+mod stats;
+mod stdin;
+mod threads;
+
+use crate::ned;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// An empty directory for the named test, under the system's temp directory.
+pub fn make_test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ned_test_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write a file with the content in the directory.
+pub fn write_test_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+    let file = dir.join(name);
+    fs::write(&file, content).unwrap();
+    file
+}
+
+/// The names of the files in the directory, sorted.
+pub fn dir_file_names(dir: &Path) -> Vec<String> {
+    let mut file_names = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    file_names.sort();
+    file_names
+}
+
+/// Run ned with the arguments, returning its exit code, and ignoring what it shows.
+pub fn run(args: &[&str]) -> i32 {
+    let args = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    let mut screen_output: Vec<u8> = vec![];
+    ned(&mut screen_output, &args).unwrap()
+}
@@ -18,6 +18,11 @@
 // 02110-1301, USA.
 //
 
+use crate::binary::Binary;
+use crate::encoding::Encoding;
+use crate::eol::Eol;
+use crate::mmap::Mmap;
+use crate::ned;
 use crate::parameters::Parameters;
 
 #[test]
@@ -81,6 +86,23 @@ fn skip_too_many() {
     test_include_match(None, 11, 100, 10, false, false);
 }
 
+#[test]
+fn format_placeholders_must_be_groups() {
+    for (format, is_valid) in [
+        ("{line}:{1}:{animal}", true),
+        ("{lne}", false),
+        ("{2}", false),
+        ("{animl}", false),
+    ] {
+        let args = ["(?P<animal>dog)", "--format", format, "test"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(ned(&mut vec![], &args).is_ok(), is_valid);
+    }
+}
+
 fn test_include_match(
     number: Option<usize>,
     skip: usize,
@@ -91,34 +113,63 @@ fn test_include_match(
 ) {
     let mut parameters = Parameters {
         all: false,
+        backup: None,
+        backup_dir: None,
         backwards: false,
+        binary: Binary::Report,
+        byte_offset: false,
+        bytes: false,
         case_replacements: false,
+        change_context: 3,
         colors: false,
+        column: None,
         context_after: 0,
         context_before: 0,
+        count: false,
+        count_matches: false,
+        crlf_regex: None,
+        diff: false,
+        encoding: Encoding::Utf8,
+        eol: Eol::Preserve,
         exclude_dirs: vec![],
         excludes: vec![],
         file_names_only: false,
         follow: false,
+        format: None,
         globs: vec![],
         group: None,
         help: false,
         ignore_non_utf8: false,
         includes: vec![],
+        interactive: None,
+        journal: None,
+        json: false,
+        line_buffered: false,
         line_numbers_only: false,
+        list_skipped_big_files: false,
+        list_skipped_binaries: false,
+        lock: false,
         matches_only: false,
+        max_filesize: None,
+        max_line_length: None,
+        mmap: Mmap::Auto,
         no_file_names: false,
         no_line_numbers: false,
         no_match: false,
         number,
+        preserve_mtime: false,
         quiet: false,
         regex: None,
         recursive: false,
         replace: None,
         skip,
+        stats: None,
         stdin: false,
         stdout: false,
+        threads: 1,
+        undo: None,
         version: false,
+        vimgrep: false,
         whole_files: false,
     };
     assert_eq!(parameters.include_match(index, count), forward_expected);
@@ -0,0 +1,452 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/rewrite.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test replacing in files on disk - atomic rewrites, etc. Each test works in its own
+/// directory under the system's temp directory, which it removes when it passes.
+use crate::rewrite::lock_file;
+use crate::snapshot::Snapshot;
+use crate::tests::{dir_file_names, make_test_dir, run, write_test_file};
+use std::fs;
+use std::path::{Component, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn replace_rewrites_file() {
+    let dir = make_test_dir("replace_rewrites_file");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+
+    let exit_code = run(&["dog", "--replace", "cat", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat is a cat.\n");
+    // No temporary files are left behind.
+    assert_eq!(dir_file_names(&dir), vec!["file.txt"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_without_matches_leaves_file_alone() {
+    let dir = make_test_dir("replace_without_matches_leaves_file_alone");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+    let modified = fs::metadata(&file).unwrap().modified().unwrap();
+
+    let exit_code = run(&["bird", "--replace", "cat", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 1);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The dog is a dog.\n");
+    assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), modified);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn replace_preserves_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = make_test_dir("replace_preserves_permissions");
+    let file = write_test_file(&dir, "script.sh", "echo dog\n");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o750)).unwrap();
+
+    let exit_code = run(&["dog", "--replace", "cat", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "echo cat\n");
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn replace_keeps_hard_links_together() {
+    let dir = make_test_dir("replace_keeps_hard_links_together");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+    let link = dir.join("link.txt");
+    fs::hard_link(&file, &link).unwrap();
+
+    let exit_code = run(&["dog", "--replace", "cat", file.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat is a cat.\n");
+    assert_eq!(fs::read_to_string(&link).unwrap(), "The cat is a cat.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn replace_through_symlink_keeps_symlink() {
+    let dir = make_test_dir("replace_through_symlink_keeps_symlink");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+    let link = dir.join("link.txt");
+    std::os::unix::fs::symlink("file.txt", &link).unwrap();
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        "--follow",
+        link.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat is a cat.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replace_preserve_mtime() {
+    let dir = make_test_dir("replace_preserve_mtime");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        "--preserve-mtime",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat is a cat.\n");
+    assert_eq!(fs::metadata(&file).unwrap().modified().unwrap(), modified);
+
+    // Without it the file is seen as changed.
+    let exit_code = run(&["cat", "--replace", "dog", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert!(fs::metadata(&file).unwrap().modified().unwrap() > modified);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn replace_preserves_extended_attributes() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = make_test_dir("replace_preserves_extended_attributes");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+    let path = CString::new(file.as_os_str().as_bytes()).unwrap();
+    let name = CString::new("user.ned.test").unwrap();
+    let value = b"dog";
+    // Safety: path and name are null terminated, and value is valid for its length.
+    let result = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+    if result != 0 {
+        // The temp directory's file system doesn't have user extended attributes.
+        fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let exit_code = run(&["dog", "--replace", "cat", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat is a cat.\n");
+    let mut buffer = [0u8; 16];
+    // Safety: path and name are null terminated, and buffer is valid for its length.
+    let size = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+        )
+    };
+    assert_eq!(size, value.len() as isize);
+    assert_eq!(&buffer[..value.len()], value);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn files_changed_since_they_were_read_are_told() {
+    let dir = make_test_dir("files_changed_since_they_were_read_are_told");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+
+    let snapshot = Snapshot::new(&fs::File::open(&file).unwrap()).unwrap();
+    assert!(snapshot.check(&file).is_ok());
+    fs::write(&file, "The dog is a dog, the cat is a cat.\n").unwrap();
+    assert!(snapshot.check(&file).is_err());
+
+    // Replaced by another file of the same size.
+    let snapshot = Snapshot::new(&fs::File::open(&file).unwrap()).unwrap();
+    let other = write_test_file(&dir, "other.txt", "The cat is a cat, the dog is a dog.\n");
+    fs::rename(&other, &file).unwrap();
+    assert!(snapshot.check(&file).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lock_waits_and_reads_the_file_that_replaced_it() {
+    let dir = make_test_dir("lock_waits_and_reads_the_file_that_replaced_it");
+    let file = write_test_file(&dir, "file.txt", "The dog.\n");
+    let held = fs::File::open(&file).unwrap();
+    lock_file(&held).unwrap();
+
+    let path = file.clone();
+    let replacing =
+        thread::spawn(move || run(&["dog", "--replace", "cat", "--lock", path.to_str().unwrap()]));
+    thread::sleep(Duration::from_millis(100));
+    let other = write_test_file(&dir, "other.txt", "The dog and the dog.\n");
+    fs::rename(&other, &file).unwrap();
+    drop(held);
+
+    assert_eq!(replacing.join().unwrap(), 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The cat and the cat.\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup_changed_files_only() {
+    let dir = make_test_dir("backup_changed_files_only");
+    let file1 = write_test_file(&dir, "file1.txt", "The dog is a dog.\n");
+    write_test_file(&dir, "file2.txt", "The bird is a bird.\n");
+
+    let exit_code = run(&["dog", "--replace", "cat", "--backup", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file1).unwrap(), "The cat is a cat.\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("file1.txt.orig")).unwrap(),
+        "The dog is a dog.\n"
+    );
+    assert_eq!(
+        dir_file_names(&dir),
+        vec!["file1.txt", "file1.txt.orig", "file2.txt"]
+    );
+    // Backups aren't searched.
+    let exit_code = run(&["dog", "--backup", dir.to_str().unwrap()]);
+    assert_eq!(exit_code, 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup_with_suffix() {
+    let dir = make_test_dir("backup_with_suffix");
+    write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        "--backup=.bak",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        fs::read_to_string(dir.join("file.txt.bak")).unwrap(),
+        "The dog is a dog.\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup_numbered() {
+    let dir = make_test_dir("backup_numbered");
+    let file = write_test_file(&dir, "file.txt", "The dog is a dog.\n");
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        "--backup=numbered",
+        dir.to_str().unwrap(),
+    ]);
+    assert_eq!(exit_code, 0);
+    let exit_code = run(&[
+        "cat",
+        "--replace",
+        "bird",
+        "--backup=numbered",
+        dir.to_str().unwrap(),
+    ]);
+    assert_eq!(exit_code, 0);
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "The bird is a bird.\n");
+    assert_eq!(
+        fs::read_to_string(dir.join("file.txt.~1~")).unwrap(),
+        "The dog is a dog.\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("file.txt.~2~")).unwrap(),
+        "The cat is a cat.\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup_dir_mirrors_tree() {
+    let dir = make_test_dir("backup_dir_mirrors_tree");
+    fs::create_dir(dir.join("sub")).unwrap();
+    write_test_file(&dir, "file1.txt", "The dog is a dog.\n");
+    write_test_file(&dir.join("sub"), "file2.txt", "A dog.\n");
+    let backup_dir = dir.join("backups");
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        "--recursive",
+        "--backup-dir",
+        backup_dir.to_str().unwrap(),
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(exit_code, 0);
+    let mirrored_dir = backup_dir.join(
+        dir.components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect::<PathBuf>(),
+    );
+    assert_eq!(
+        fs::read_to_string(mirrored_dir.join("file1.txt")).unwrap(),
+        "The dog is a dog.\n"
+    );
+    assert_eq!(
+        fs::read_to_string(mirrored_dir.join("sub").join("file2.txt")).unwrap(),
+        "A dog.\n"
+    );
+    // The backup directory isn't searched.
+    let exit_code = run(&[
+        "dog",
+        "--recursive",
+        "--backup-dir",
+        backup_dir.to_str().unwrap(),
+        dir.to_str().unwrap(),
+    ]);
+    assert_eq!(exit_code, 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn journal_and_undo() {
+    let dir = make_test_dir("journal_and_undo");
+    let files_dir = dir.join("files");
+    fs::create_dir(&files_dir).unwrap();
+    let file1 = write_test_file(&files_dir, "file1.txt", "The dog is a dog.\n");
+    let file2 = write_test_file(&files_dir, "file2.txt", "A dog.\n");
+    let journal_dir = dir.join("journal");
+    let journal_option = format!("--journal={}", journal_dir.to_str().unwrap());
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        &journal_option,
+        files_dir.to_str().unwrap(),
+    ]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file1).unwrap(), "The cat is a cat.\n");
+    assert_eq!(fs::read_to_string(&file2).unwrap(), "A cat.\n");
+    assert_eq!(dir_file_names(&journal_dir).len(), 1);
+
+    let exit_code = run(&["--undo", &journal_option]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file1).unwrap(), "The dog is a dog.\n");
+    assert_eq!(fs::read_to_string(&file2).unwrap(), "A dog.\n");
+    // The run is gone once it has been undone.
+    assert!(dir_file_names(&journal_dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn undo_file_replaced_in_twice() {
+    let dir = make_test_dir("undo_file_replaced_in_twice");
+    let file = write_test_file(&dir, "file.txt", "aaa");
+    let path = file.to_str().unwrap();
+    let journal_dir = dir.join("journal");
+    let journal_option = format!("--journal={}", journal_dir.to_str().unwrap());
+
+    let exit_code = run(&[
+        "-n",
+        "1",
+        "a",
+        "--replace",
+        "b",
+        &journal_option,
+        path,
+        path,
+    ]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "bba");
+
+    let exit_code = run(&["--undo", &journal_option]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "aaa");
+    assert!(dir_file_names(&journal_dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn undo_refuses_changed_files() {
+    let dir = make_test_dir("undo_refuses_changed_files");
+    let files_dir = dir.join("files");
+    fs::create_dir(&files_dir).unwrap();
+    let file1 = write_test_file(&files_dir, "file1.txt", "The dog is a dog.\n");
+    let file2 = write_test_file(&files_dir, "file2.txt", "A dog.\n");
+    let journal_dir = dir.join("journal");
+    let journal_option = format!("--journal={}", journal_dir.to_str().unwrap());
+
+    let exit_code = run(&[
+        "dog",
+        "--replace",
+        "cat",
+        &journal_option,
+        files_dir.to_str().unwrap(),
+    ]);
+    assert_eq!(exit_code, 0);
+    fs::write(&file1, "Edited since.\n").unwrap();
+    let run_id = dir_file_names(&journal_dir).pop().unwrap();
+
+    let undo_option = format!("--undo={}", run_id);
+    let exit_code = run(&[&undo_option, &journal_option]);
+    assert_eq!(exit_code, 1);
+    assert_eq!(fs::read_to_string(&file1).unwrap(), "Edited since.\n");
+    assert_eq!(fs::read_to_string(&file2).unwrap(), "A dog.\n");
+    // The run is kept for the file that wasn't restored.
+    assert_eq!(dir_file_names(&journal_dir), vec![run_id]);
+
+    // Once the file is put back it can be restored.
+    fs::write(&file1, "The cat is a cat.\n").unwrap();
+    let exit_code = run(&["--undo", &journal_option]);
+    assert_eq!(exit_code, 0);
+    assert_eq!(fs::read_to_string(&file1).unwrap(), "The dog is a dog.\n");
+    assert!(dir_file_names(&journal_dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,77 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/stats.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test what --stats counts.
+use crate::options_with_defaults::OptionsWithDefaults;
+use crate::opts::make_opts;
+use crate::parameters::get_parameters;
+use crate::process_files;
+use crate::stats::Counter;
+use crate::tests::{make_test_dir, write_test_file};
+use std::fs;
+
+#[test]
+fn stats_count_what_the_run_did() {
+    let dir = make_test_dir("stats_count_what_the_run_did");
+    write_test_file(&dir, "file1.txt", "The dog is a dog.\n");
+    write_test_file(&dir, "file2.txt", "The dog.\n");
+    write_test_file(&dir, "file3.txt", "The bird.\n");
+    write_test_file(&dir, "file.htm", "The dog.\n");
+    write_test_file(&dir, ".hidden.txt", "The dog.\n");
+
+    let args = [
+        "dog",
+        "--replace",
+        "cat",
+        "--exclude",
+        "*.htm",
+        "--stats=json",
+        "-j",
+        "2",
+        dir.to_str().unwrap(),
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect::<Vec<String>>();
+    let options_with_defaults = OptionsWithDefaults::new(make_opts(), &args).unwrap();
+    let parameters = get_parameters(&options_with_defaults).unwrap();
+    assert!(process_files(&mut vec![], &parameters).unwrap());
+
+    let stats = parameters.stats.as_ref().unwrap();
+    for (counter, expected) in [
+        (Counter::FilesWalked, 5),
+        (Counter::SkippedHidden, 1),
+        (Counter::SkippedExcluded, 1),
+        (Counter::FilesMatched, 2),
+        (Counter::Matches, 3),
+        (Counter::Replacements, 3),
+        (Counter::FilesModified, 2),
+        (Counter::BytesRead, 37),
+        (Counter::BytesWritten, 27),
+    ] {
+        assert_eq!(stats.get(counter), expected, "{:?}", counter);
+    }
+    let mut stats_output: Vec<u8> = vec![];
+    stats.write(&mut stats_output).unwrap();
+    let stats_output = String::from_utf8(stats_output).unwrap();
+    assert!(stats_output.starts_with("{\"files_walked\":5,\"skipped_hidden\":1,"));
+    assert!(stats_output.ends_with("}\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
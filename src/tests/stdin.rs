@@ -0,0 +1,134 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/stdin.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+use crate::ned_error::NedResult;
+/// Test that stdin is processed as it is read, so that ned can sit in a pipeline that doesn't
+/// end.
+use crate::options_with_defaults::OptionsWithDefaults;
+use crate::opts::make_opts;
+use crate::parameters::get_parameters;
+use crate::process_file;
+use crate::source::Source;
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, Read, Write};
+
+#[test]
+fn stdin_is_searched_as_it_is_read() {
+    let mut output = Output::default();
+    let result = run(
+        &["match"],
+        &["one\n", "two match\n", "three\n"],
+        &mut output,
+    );
+
+    assert!(result.is_err());
+    assert_eq!(String::from_utf8(output.written).unwrap(), "2:two match\n");
+}
+
+#[test]
+fn line_buffered_flushes_every_line() {
+    let mut output = Output::default();
+    let result = run(
+        &["match", "--line-buffered"],
+        &["one match\n", "two\n", "three match\n"],
+        &mut output,
+    );
+
+    assert!(result.is_err());
+    assert_eq!(
+        String::from_utf8(output.written).unwrap(),
+        "1:one match\n3:three match\n"
+    );
+    assert_eq!(output.flushed_lens, vec![12, 12, 26]);
+}
+
+#[test]
+fn line_buffered_replaces_in_stdin_a_line_at_a_time() {
+    let mut output = Output::default();
+    let result = run(
+        &["o", "--replace=0", "--line-buffered"],
+        &["one\n", "two\n"],
+        &mut output,
+    );
+
+    assert!(result.is_err());
+    assert_eq!(String::from_utf8(output.written).unwrap(), "0ne\ntw0\n");
+}
+
+#[test]
+fn replace_in_stdin_waits_for_the_end_without_line_buffered() {
+    let mut output = Output::default();
+    let result = run(&["o", "--replace=0"], &["one\n", "two\n"], &mut output);
+
+    assert!(result.is_err());
+    assert_eq!(String::from_utf8(output.written).unwrap(), "");
+}
+
+/// Process stdin that gives the chunks, a read at a time, and then fails as if the pipe were
+/// still open, with no more to read.
+fn run(args: &[&str], chunks: &[&'static str], output: &mut Output) -> NedResult<bool> {
+    let args = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    unsafe { env::set_var("NED_DEFAULTS", "") };
+    let options_with_defaults = OptionsWithDefaults::new(make_opts(), &args).unwrap();
+    let parameters = get_parameters(&options_with_defaults).unwrap();
+    let pipe = Pipe {
+        chunks: chunks.iter().map(|chunk| chunk.as_bytes()).collect(),
+    };
+    let mut source = Source::Stdin(Box::new(pipe));
+    process_file(output, &parameters, &None, &mut source)
+}
+
+struct Pipe {
+    chunks: VecDeque<&'static [u8]>,
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.chunks.pop_front() {
+            Some(chunk) => {
+                buf[..chunk.len()].copy_from_slice(chunk);
+                Ok(chunk.len())
+            }
+            None => Err(io::Error::other("the pipe is still open")),
+        }
+    }
+}
+
+/// Output that remembers how much had been written each time it was flushed.
+#[derive(Default)]
+struct Output {
+    written: Vec<u8>,
+    flushed_lens: Vec<usize>,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed_lens.push(self.written.len());
+        Ok(())
+    }
+}
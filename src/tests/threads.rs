@@ -0,0 +1,83 @@
+//
+// ned, https://github.com/nevdelap/ned, tests/threads.rs
+//
+// Copyright 2016-2024 Nev Delap (nevdelap at gmail)
+//
+// This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation; either version 3, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License
+// along with this program; if not, write to the Free Software
+// Foundation, Inc., 51 Franklin Street - Fifth Floor, Boston, MA
+// 02110-1301, USA.
+//
+
+/// Test processing files a few at a time with -j/--threads.
+use crate::ned;
+use crate::tests::{make_test_dir, run, write_test_file};
+use std::fs;
+
+#[test]
+fn threads_show_files_in_the_same_order() {
+    let dir = make_test_dir("threads_show_files_in_the_same_order");
+    for number in 0..50 {
+        // Files of different lengths take different times.
+        let content = "The dog is not a cat.\n".repeat(1 + number % 7 * 100);
+        write_test_file(&dir, &format!("file{:02}.txt", number), &content);
+    }
+
+    let search = |threads: &str| {
+        let mut screen_output: Vec<u8> = vec![];
+        let args = ["dog", "--threads", threads, dir.to_str().unwrap()]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+        let exit_code = ned(&mut screen_output, &args).unwrap();
+        (exit_code, String::from_utf8(screen_output).unwrap())
+    };
+    let (exit_code, output) = search("1");
+    assert_eq!(exit_code, 0);
+    assert_eq!(search("8"), (exit_code, output));
+    let mut screen_output: Vec<u8> = vec![];
+    let args = ["bird", "-j", "8", dir.to_str().unwrap()]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(ned(&mut screen_output, &args).unwrap(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn threads_replace_in_every_file() {
+    let dir = make_test_dir("threads_replace_in_every_file");
+    for number in 0..20 {
+        write_test_file(&dir, &format!("file{:02}.txt", number), "The dog.\n");
+    }
+
+    let exit_code = run(&["dog", "--replace", "cat", "-j", "4", dir.to_str().unwrap()]);
+
+    assert_eq!(exit_code, 0);
+    for number in 0..20 {
+        assert_eq!(
+            fs::read_to_string(dir.join(format!("file{:02}.txt", number))).unwrap(),
+            "The cat.\n"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn threads_cannot_be_zero() {
+    let args = ["dog", "--threads", "0", "file.txt"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>();
+
+    assert!(ned(&mut vec![], &args).is_err());
+}